@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::{Config, TargetedTransforms, TransformConfig};
+use crate::transform::split_json_path;
+
+pub const AUDIT_FILE: &str = "audit.toml";
+
+/// record of what was masked in an export
+#[derive(Serialize)]
+pub struct Audit {
+    pub target_table: String,
+    pub exported_at: u64,
+    pub config_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_id: Option<String>,
+    pub tables: Vec<TableAudit>,
+}
+
+#[derive(Serialize)]
+pub struct TableAudit {
+    pub file: String,
+    pub table: String,
+    pub rows: u64,
+    pub columns: Vec<ColumnAudit>,
+}
+
+#[derive(Serialize)]
+pub struct ColumnAudit {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub affected_rows: Option<u64>,
+}
+
+impl Audit {
+    pub fn new(cfg: &Config, tables: Vec<TableAudit>) -> Result<Self> {
+        let exported_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Ok(Self {
+            target_table: cfg.target_table()?.to_owned(),
+            exported_at,
+            config_hash: config_hash(cfg)?,
+            seed_id: cfg
+                .export
+                .as_ref()
+                .and_then(|e| e.seed.as_deref())
+                .map(seed_id),
+            tables,
+        })
+    }
+
+    pub async fn write(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(AUDIT_FILE);
+        let content = toml::to_string(self).with_context(|| "unable to serialize audit")?;
+        tokio::fs::write(&path, content)
+            .await
+            .with_context(|| format!("unable to write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// sha256 of the config, leaving out the connections, their credentials and the seed
+fn config_hash(cfg: &Config) -> Result<String> {
+    let mut value = serde_json::to_value(cfg).with_context(|| "unable to serialize config")?;
+    if let Some(object) = value.as_object_mut() {
+        for key in ["database_url", "password_file", "source", "destination"] {
+            object.remove(key);
+        }
+        if let Some(export) = object.get_mut("export").and_then(|e| e.as_object_mut()) {
+            export.remove("seed");
+        }
+    }
+    // json objects are sorted by key, so the hash doesn't depend on hashmap ordering
+    Ok(format!("{:x}", Sha256::digest(value.to_string())))
+}
+
+/// identifies the seed without revealing it: a prefix of its sha256
+fn seed_id(seed: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(format!("pgsubset seed id\0{seed}")));
+    digest[..16].to_owned()
+}
+
+/// describes the transforms applied to each column, by table
+pub fn describe_transforms(
+    transforms: &TargetedTransforms,
+) -> HashMap<String, HashMap<String, String>> {
+    transforms
+        .iter()
+        .map(|(table, configs)| {
+            let mut columns: HashMap<String, Vec<String>> = HashMap::new();
+            for (key, config) in configs {
+                match split_json_path(key) {
+                    Some((column, path)) => columns
+                        .entry(column.to_owned())
+                        .or_default()
+                        .push(format!("$.{}: {}", path.join("."), describe(config))),
+                    None => columns
+                        .entry(key.clone())
+                        .or_default()
+                        .push(describe(config)),
+                }
+            }
+            let columns = columns
+                .into_iter()
+                .map(|(column, mut descriptions)| {
+                    descriptions.sort();
+                    (column, descriptions.join("; "))
+                })
+                .collect();
+            (table.clone(), columns)
+        })
+        .collect()
+}
+
+fn describe(config: &TransformConfig) -> String {
+    match serde_json::to_value(config) {
+        Ok(serde_json::Value::String(name)) => name,
+        Ok(value) => value.to_string(),
+        Err(err) => format!("unknown ({err})"),
+    }
+}
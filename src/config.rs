@@ -1,30 +1,449 @@
-use serde::Deserialize;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, IntoDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-pub type TargetedTransforms = HashMap<String, HashMap<String, TransformKind>>;
+pub type TargetedTransforms = HashMap<String, HashMap<String, TransformConfig>>;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Default)]
 pub struct Config {
-    pub target_table: String,
+    pub target_table: Option<String>,
+    pub target_filter: Option<String>,
     pub target_dir: String,
+    #[serde(default)]
     pub database_url: String,
+    pub password_file: Option<String>,
+    pub source: Option<ConnectionOptions>,
+    pub destination: Option<ConnectionOptions>,
     pub m2m_tables: Option<Vec<M2MTable>>,
+    pub virtual_relationships: Option<Vec<VirtualRelationship>>,
+    pub generic_relationships: Option<Vec<GenericRelationship>>,
+    pub ignored_relationships: Option<Vec<IgnoredRelationship>>,
     pub transforms: Option<TargetedTransforms>,
+    pub export: Option<ExportOptions>,
+    pub import: Option<ImportOptions>,
 }
 
-#[derive(Deserialize)]
+impl Config {
+    /// reads a config file, resolving its includes, the selected profile, the overrides,
+    /// `${VAR}` references in the connection settings and seed and the password file
+    pub fn load(
+        path: Option<&Path>,
+        profile: Option<&str>,
+        overrides: Vec<toml::Value>,
+    ) -> Result<Self> {
+        let mut value = match path {
+            Some(path) => read_value(path, &mut Vec::new())?,
+            None => toml::Value::Table(Default::default()),
+        };
+        let profiles = value.as_table_mut().and_then(|t| t.remove("profiles"));
+        if let Some(profile) = profile {
+            let overrides = profiles
+                .as_ref()
+                .and_then(|p| p.get(profile))
+                .cloned()
+                .ok_or_else(|| anyhow!("unknown profile {profile}"))?;
+            merge(&mut value, overrides, false);
+        }
+        for overrides in overrides {
+            merge(&mut value, overrides, false);
+        }
+        for path in INTERPOLATED {
+            if let Some(setting) = path.iter().try_fold(&mut value, |v, key| v.get_mut(key)) {
+                interpolate(setting)?;
+            }
+        }
+
+        let mut cfg: Config = value.try_into().with_context(|| match path {
+            Some(path) => format!("unable to parse {}", path.display()),
+            None => "unable to build config from the arguments".to_owned(),
+        })?;
+        if let Some(file) = &cfg.password_file {
+            let password = std::fs::read_to_string(file)
+                .with_context(|| format!("unable to read password file {file}"))?;
+            cfg.database_url = with_password(&cfg.database_url, password.trim_end())?;
+        }
+        Ok(cfg)
+    }
+
+    /// the table the subset is built around, which every subcommand but import needs
+    pub fn target_table(&self) -> Result<&str> {
+        self.target_table.as_deref().ok_or_else(|| {
+            anyhow!("target_table is not set, add it to the config or pass --target-table")
+        })
+    }
+
+    /// connection exported from, defaulting to `database_url`
+    pub fn source(&self) -> ConnectionOptions {
+        self.connection(self.source.as_ref())
+    }
+
+    /// connection imported into, defaulting to `database_url`
+    pub fn destination(&self) -> ConnectionOptions {
+        self.connection(self.destination.as_ref())
+    }
+
+    fn connection(&self, options: Option<&ConnectionOptions>) -> ConnectionOptions {
+        let mut options = options.cloned().unwrap_or_default();
+        if options.url.is_none() && !self.database_url.is_empty() {
+            options.url = Some(self.database_url.clone());
+        }
+        options
+    }
+}
+
+/// parses `key=value` into a table, the key being a toml (dotted) key and the value a toml
+/// value, or a string when it isn't one
+pub fn parse_override(assignment: &str) -> Result<toml::Value> {
+    let (key, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| anyhow!("{assignment} should be written as key=value"))?;
+    toml::from_str(&format!("{key} = {value}"))
+        .or_else(|_| toml::from_str(&format!("{key} = {}", toml::Value::from(value))))
+        .with_context(|| format!("invalid key in {assignment}"))
+}
+
+/// parses a config file merged over the files it includes, relative to its directory
+fn read_value(path: &Path, stack: &mut Vec<PathBuf>) -> Result<toml::Value> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("unable to read {}", path.display()))?;
+    if stack.contains(&canonical) {
+        bail!("{} includes itself", path.display());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("unable to read {}", path.display()))?;
+    let mut value: toml::Value =
+        toml::from_str(&content).with_context(|| format!("unable to parse {}", path.display()))?;
+
+    let includes = match value.as_table_mut().and_then(|t| t.remove("include")) {
+        None => Vec::new(),
+        Some(toml::Value::String(include)) => vec![include],
+        Some(toml::Value::Array(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                toml::Value::String(include) => Ok(include),
+                _ => Err(anyhow!("include of {} should be paths", path.display())),
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => bail!("include of {} should be paths", path.display()),
+    };
+
+    stack.push(canonical);
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut merged = toml::Value::Table(Default::default());
+    for include in includes {
+        merge(&mut merged, read_value(&dir.join(include), stack)?, true);
+    }
+    stack.pop();
+
+    merge(&mut merged, value, true);
+    Ok(merged)
+}
+
+/// merges tables key by key, the overlay winning for other values, unless arrays are concatenated
+fn merge(base: &mut toml::Value, overlay: toml::Value, concat_arrays: bool) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value, concat_arrays),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (toml::Value::Array(base), toml::Value::Array(overlay)) if concat_arrays => {
+            base.extend(overlay)
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// the settings `${VAR}` is replaced in, other strings such as regex replacements keep their `${name}`
+const INTERPOLATED: [&[&str]; 5] = [
+    &["database_url"],
+    &["password_file"],
+    &["source"],
+    &["destination"],
+    &["export", "seed"],
+];
+
+/// replaces `${VAR}` in string values with the environment variable
+fn interpolate(value: &mut toml::Value) -> Result<()> {
+    match value {
+        toml::Value::String(s) if s.contains("${") => {
+            let re = Regex::new(r"\$\{(\w+)\}")?;
+            let mut missing = None;
+            let replaced = re.replace_all(s, |caps: &regex::Captures| {
+                std::env::var(&caps[1]).unwrap_or_else(|_| {
+                    missing.get_or_insert_with(|| caps[1].to_owned());
+                    String::new()
+                })
+            });
+            if let Some(var) = missing {
+                bail!("environment variable {var} is not set");
+            }
+            *s = replaced.into_owned();
+        }
+        toml::Value::Array(values) => values.iter_mut().try_for_each(interpolate)?,
+        toml::Value::Table(table) => table.iter_mut().try_for_each(|(_, v)| interpolate(v))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn with_password(url: &str, password: &str) -> Result<String> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| anyhow!("database_url should be a postgres:// url"))?;
+    let (user, host) = rest
+        .split_once('@')
+        .filter(|(user, _)| !user.contains('/'))
+        .ok_or_else(|| anyhow!("password_file needs a user in database_url"))?;
+    if user.contains(':') {
+        bail!("database_url already has a password");
+    }
+
+    let password: String = password
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    Ok(format!("{scheme}://{user}:{password}@{host}"))
+}
+
+/// settings use the names of their libpq counterparts
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectionOptions {
+    pub url: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub password_file: Option<String>,
+    pub passfile: Option<String>,
+    pub dbname: Option<String>,
+    pub sslmode: Option<String>,
+    pub sslrootcert: Option<String>,
+    pub sslcert: Option<String>,
+    pub sslkey: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct M2MTable {
     pub name: String,
     pub source: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
+pub struct VirtualRelationship {
+    pub source_table: String,
+    pub source_columns: Vec<String>,
+    pub dest_table: String,
+    pub dest_columns: Vec<String>,
+    pub discriminator: Option<Discriminator>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Discriminator {
+    pub column: String,
+    pub value: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GenericRelationship {
+    pub source_table: String,
+    pub type_column: String,
+    pub id_column: String,
+    pub dest_column: Option<String>,
+    pub targets: Option<HashMap<String, String>>,
+    pub lookup_query: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct IgnoredRelationship {
+    pub constraint: Option<String>,
+    pub column: Option<String>,
+    #[serde(default)]
+    pub nullify: bool,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct ExportOptions {
+    #[serde(default)]
+    pub ddl: bool,
+    pub partitions: Option<HashMap<String, Vec<String>>>,
+    #[serde(default)]
+    pub fail_on_pii: bool,
+    pub seed: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct ImportOptions {
+    pub clean: Option<CleanMode>,
+    #[serde(default)]
+    pub remap_keys: bool,
+    #[serde(default)]
+    pub ignore_extra_columns: bool,
+    #[serde(default)]
+    pub fill_missing_columns: bool,
+    #[serde(default)]
+    pub apply_ddl: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanMode {
+    Truncate,
+    Delete,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum TransformConfig {
+    Named(TransformKind),
+    Parameterised(ParameterisedTransform),
+}
+
+// written by hand rather than untagged so a typo reports the unknown variant and the expected ones
+impl<'de> Deserialize<'de> for TransformConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TransformVisitor;
+
+        impl<'de> Visitor<'de> for TransformVisitor {
+            type Value = TransformConfig;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a transform name or a table with a transform kind")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                TransformKind::deserialize(name.into_deserializer()).map(TransformConfig::Named)
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, map: M) -> Result<Self::Value, M::Error> {
+                ParameterisedTransform::deserialize(MapAccessDeserializer::new(map))
+                    .map(TransformConfig::Parameterised)
+            }
+        }
+
+        deserializer.deserialize_any(TransformVisitor)
+    }
+}
+
+impl From<TransformKind> for TransformConfig {
+    fn from(kind: TransformKind) -> Self {
+        Self::Named(kind)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ParameterisedTransform {
+    Constant {
+        value: String,
+    },
+    RegexReplace {
+        pattern: String,
+        replacement: String,
+    },
+    Truncate {
+        len: usize,
+    },
+    NumericNoise {
+        pct: f64,
+    },
+    DateShift {
+        max_days: u32,
+    },
+    Round {
+        digits: i32,
+    },
+    JsonKeys {
+        keys: Vec<String>,
+        transform: Box<TransformConfig>,
+    },
+    ArrayElements {
+        transform: Box<TransformConfig>,
+    },
+    Template {
+        template: String,
+    },
+    Conditional {
+        column: String,
+        equals: String,
+        transform: Box<TransformConfig>,
+        otherwise: Option<Box<TransformConfig>>,
+    },
+    Custom {
+        name: String,
+    },
+    Sql {
+        expression: String,
+    },
+}
+
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TransformKind {
+    SetNull,
     ClearField,
     FirstNameEn,
+    FirstNameFr,
+    FirstNameIt,
+    FirstNameDe,
     LastNameEn,
+    LastNameFr,
+    LastNameIt,
+    LastNameDe,
+    NameEn,
+    NameFr,
+    NameIt,
+    NameDe,
     UsernameEn,
     EmailEn,
+    EmailFr,
+    EmailIt,
+    EmailDe,
+    PhoneNumberEn,
+    PhoneNumberFr,
+    PhoneNumberIt,
+    PhoneNumberDe,
+    StreetAddressEn,
+    StreetAddressFr,
+    StreetAddressIt,
+    StreetAddressDe,
+    CityEn,
+    CityFr,
+    CityIt,
+    CityDe,
+    ZipCodeEn,
+    ZipCodeFr,
+    ZipCodeIt,
+    ZipCodeDe,
+    CompanyNameEn,
+    CompanyNameFr,
+    CompanyNameIt,
+    CompanyNameDe,
+    Iban,
+    Ipv4,
+    Ipv6,
+    Uuid,
+    DateJitter,
+    CreditCardNumber,
+    LoremWord,
+    LoremSentence,
+    LoremParagraph,
     DjangoGarbagePassword,
 }
@@ -0,0 +1,199 @@
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use percent_encoding::percent_decode_str;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgSslMode};
+use sqlx::{Pool, Postgres};
+use url::Url;
+
+use crate::config::ConnectionOptions;
+
+const DEFAULT_PORT: u16 = 5432;
+
+/// connects with the options, falling back to the url, then to the `PG*` environment variables
+/// and to a password file for the password, like libpq does
+pub async fn connect(options: &ConnectionOptions) -> Result<Pool<Postgres>> {
+    let connect_options = connect_options(options)?;
+    PgPoolOptions::new()
+        .max_connections(5)
+        .connect_with(connect_options)
+        .await
+        .with_context(|| "unable to connect to database")
+}
+
+/// host the options resolve to, empty for the default local socket
+pub fn host(options: &ConnectionOptions) -> Result<String> {
+    Ok(Params::resolve(options)?.host.unwrap_or_default())
+}
+
+fn connect_options(options: &ConnectionOptions) -> Result<PgConnectOptions> {
+    // the url also carries settings like application_name in its query
+    let mut connect_options = match &options.url {
+        Some(url) => PgConnectOptions::from_str(url).with_context(|| "invalid database url")?,
+        None => PgConnectOptions::new_without_pgpass(),
+    };
+    let params = Params::resolve(options)?;
+    match &params.host {
+        Some(socket) if socket.starts_with('/') => connect_options = connect_options.socket(socket),
+        Some(host) => connect_options = connect_options.host(host),
+        None => {}
+    }
+    if let Some(port) = params.port {
+        connect_options = connect_options.port(port);
+    }
+    if let Some(user) = &params.user {
+        connect_options = connect_options.username(user);
+    }
+    if let Some(dbname) = &params.dbname {
+        connect_options = connect_options.database(dbname);
+    }
+    if let Some(password) = &params.password {
+        connect_options = connect_options.password(password);
+    }
+
+    if let Some(mode) = &options.sslmode {
+        let mode = PgSslMode::from_str(mode).with_context(|| format!("invalid sslmode {mode}"))?;
+        connect_options = connect_options.ssl_mode(mode);
+    }
+    if let Some(cert) = &options.sslrootcert {
+        connect_options = connect_options.ssl_root_cert(cert);
+    }
+    if let Some(cert) = &options.sslcert {
+        connect_options = connect_options.ssl_client_cert(cert);
+    }
+    if let Some(key) = &options.sslkey {
+        connect_options = connect_options.ssl_client_key(key);
+    }
+    Ok(connect_options)
+}
+
+/// the connection parameters the options resolve to, before the driver's own defaults
+pub struct Params {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub dbname: Option<String>,
+}
+
+impl Params {
+    pub fn resolve(options: &ConnectionOptions) -> Result<Self> {
+        let url = options
+            .url
+            .as_deref()
+            .map(Url::parse)
+            .transpose()
+            .with_context(|| "invalid database url")?;
+        // a parameter of the url is either in its query or in the url itself
+        let from_url = |key: &str, part: fn(&Url) -> Option<String>| {
+            let url = url.as_ref()?;
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.into_owned())
+                .or_else(|| part(url))
+                .filter(|value| !value.is_empty())
+        };
+
+        let host = options
+            .host
+            .clone()
+            .or_else(|| from_url("host", |url| url.host_str().map(str::to_owned)))
+            .or_else(|| env_var("PGHOST"));
+        let port = options
+            .port
+            .map(|port| port.to_string())
+            .or_else(|| from_url("port", |url| url.port().map(|port| port.to_string())))
+            .or_else(|| env_var("PGPORT"))
+            .map(|port| port.parse().with_context(|| format!("invalid port {port}")))
+            .transpose()?;
+        let user = options
+            .user
+            .clone()
+            .or_else(|| from_url("user", |url| Some(decode(url.username()))))
+            .or_else(|| env_var("PGUSER"));
+        let dbname = options
+            .dbname
+            .clone()
+            .or_else(|| {
+                from_url("dbname", |url| {
+                    url.path()
+                        .strip_prefix('/')
+                        .filter(|s| !s.is_empty())
+                        .map(decode)
+                })
+            })
+            .or_else(|| env_var("PGDATABASE"));
+
+        let mut params = Self {
+            host,
+            port,
+            user,
+            password: None,
+            dbname,
+        };
+        params.password = match (&options.password, &options.password_file) {
+            (Some(password), _) => Some(password.clone()),
+            (None, Some(file)) => {
+                let password = std::fs::read_to_string(file)
+                    .with_context(|| format!("unable to read password file {file}"))?;
+                Some(password.trim_end().to_owned())
+            }
+            (None, None) => from_url("password", |url| url.password().map(decode))
+                .or_else(|| env_var("PGPASSWORD"))
+                .or_else(|| params.passfile_password(options.passfile.as_deref())),
+        };
+        Ok(params)
+    }
+
+    /// looks the password up in `passfile`, `PGPASSFILE` or `~/.pgpass`
+    fn passfile_password(&self, passfile: Option<&str>) -> Option<String> {
+        let path = passfile
+            .map(PathBuf::from)
+            .or_else(|| env_var("PGPASSFILE").map(PathBuf::from))
+            .or_else(|| env_var("HOME").map(|home| PathBuf::from(home).join(".pgpass")))?;
+        let content = std::fs::read_to_string(path).ok()?;
+
+        let host = self.host.as_deref().unwrap_or("localhost");
+        let port = self.port.unwrap_or(DEFAULT_PORT).to_string();
+        let user = self.user.clone().or_else(|| env_var("USER"))?;
+        let dbname = self.dbname.as_deref().unwrap_or(&user);
+        let wanted = [host, port.as_str(), dbname, user.as_str()];
+
+        content
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(split_passfile_line)
+            .find(|fields| {
+                fields.len() == 5
+                    && fields
+                        .iter()
+                        .zip(wanted)
+                        .all(|(field, wanted)| field == "*" || field == wanted)
+            })
+            .map(|mut fields| fields.remove(4))
+    }
+}
+
+/// splits on `:`, which can be escaped with `\` like `\` itself
+fn split_passfile_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => fields.last_mut().unwrap().extend(chars.next()),
+            ':' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+fn decode(part: &str) -> String {
+    percent_decode_str(part).decode_utf8_lossy().into_owned()
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
@@ -0,0 +1,50 @@
+//! rows as produced and consumed by `COPY ... CSV`, where an unquoted empty field is NULL and `""` is an empty string
+
+pub fn parse_row(row: &str) -> Vec<Option<String>> {
+    let row = row.strip_suffix('\n').unwrap_or(row);
+    let row = row.strip_suffix('\r').unwrap_or(row);
+    let mut chars = row.chars().peekable();
+    let mut fields = Vec::new();
+
+    loop {
+        let mut field = String::new();
+        let quoted = chars.next_if_eq(&'"').is_some();
+        if quoted {
+            while let Some(c) = chars.next() {
+                if c != '"' {
+                    field.push(c);
+                } else if chars.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                } else {
+                    break;
+                }
+            }
+        }
+        while let Some(c) = chars.next_if(|c| *c != ',') {
+            field.push(c);
+        }
+
+        fields.push((quoted || !field.is_empty()).then_some(field));
+        if chars.next().is_none() {
+            return fields;
+        }
+    }
+}
+
+pub fn write_row(fields: &[Option<String>]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|field| match field.as_deref() {
+            None => String::new(),
+            Some(value) if needs_quotes(value) => format!("\"{}\"", value.replace('"', "\"\"")),
+            Some(value) => value.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+fn needs_quotes(value: &str) -> bool {
+    value.is_empty() || value == "\\." || value.contains([',', '"', '\n', '\r'])
+}
@@ -0,0 +1,254 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use sqlx::{Executor, Pool, Postgres};
+
+pub const DDL_FILE: &str = "schema.sql";
+
+const TABLES: &str = "
+SELECT cls.oid
+FROM pg_class cls
+JOIN pg_namespace nsp ON nsp.oid = cls.relnamespace
+WHERE nsp.nspname NOT IN ('information_schema', 'pg_catalog')
+AND cls.relkind IN ('r', 'p')
+AND cls.relname = ANY($1)";
+
+async fn generate(pool: &Pool<Postgres>, tables: &[String]) -> Result<String> {
+    let mut statements = Vec::new();
+
+    statements.extend(
+        fetch_statements(
+            pool,
+            tables,
+            &format!(
+                "
+SELECT DISTINCT format('CREATE EXTENSION IF NOT EXISTS %I;', e.extname)
+FROM pg_extension e
+JOIN pg_depend d ON d.refobjid = e.oid AND d.deptype = 'e'
+WHERE (
+ d.classid = 'pg_type'::regclass
+ AND d.objid IN (
+  SELECT t.oid
+  FROM pg_attribute a
+  JOIN pg_type t ON t.oid = a.atttypid OR t.typarray = a.atttypid
+  WHERE a.attrelid IN ({TABLES})
+ )
+) OR (
+ d.classid = 'pg_proc'::regclass
+ AND d.objid IN (
+  SELECT dep.refobjid
+  FROM pg_attrdef ad
+  JOIN pg_depend dep ON dep.objid = ad.oid AND dep.classid = 'pg_attrdef'::regclass
+  WHERE dep.refclassid = 'pg_proc'::regclass
+  AND ad.adrelid IN ({TABLES})
+ )
+)"
+            ),
+        )
+        .await
+        .with_context(|| "unable to fetch extensions")?,
+    );
+
+    statements.extend(
+        fetch_statements(
+            pool,
+            tables,
+            &format!(
+                "
+SELECT format('CREATE TYPE %I AS ENUM (%s);', t.typname, string_agg(quote_literal(e.enumlabel), ', ' ORDER BY e.enumsortorder))
+FROM pg_type t
+JOIN pg_enum e ON e.enumtypid = t.oid
+WHERE t.oid IN (
+ SELECT t.oid
+ FROM pg_attribute a
+ JOIN pg_type t ON t.oid = a.atttypid OR t.typarray = a.atttypid
+ WHERE a.attrelid IN ({TABLES})
+)
+GROUP BY t.typname"
+            ),
+        )
+        .await
+        .with_context(|| "unable to fetch enum types")?,
+    );
+
+    let sequences = format!(
+        "
+FROM pg_sequence sq
+JOIN pg_class s ON s.oid = sq.seqrelid
+JOIN pg_depend d ON d.objid = s.oid
+ AND d.classid = 'pg_class'::regclass
+ AND d.refclassid = 'pg_class'::regclass
+ AND d.deptype = 'a'
+JOIN pg_class t ON t.oid = d.refobjid
+JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = d.refobjsubid
+WHERE t.oid IN ({TABLES})
+ORDER BY s.relname"
+    );
+    statements.extend(
+        fetch_statements(
+            pool,
+            tables,
+            &format!(
+                "
+SELECT format(
+ 'CREATE SEQUENCE %I AS %s INCREMENT BY %s MINVALUE %s MAXVALUE %s START WITH %s%s;',
+ s.relname, format_type(sq.seqtypid, NULL), sq.seqincrement, sq.seqmin, sq.seqmax, sq.seqstart,
+ CASE WHEN sq.seqcycle THEN ' CYCLE' ELSE '' END
+)
+{sequences}"
+            ),
+        )
+        .await
+        .with_context(|| "unable to fetch sequences")?,
+    );
+
+    statements.extend(
+        fetch_statements(
+            pool,
+            tables,
+            &format!(
+                "
+SELECT format(E'CREATE TABLE %I (\\n    %s\\n)%s;', c.relname, string_agg(
+ format('%I %s%s%s%s',
+  a.attname,
+  format_type(a.atttypid, a.atttypmod),
+  CASE a.attidentity
+   WHEN 'a' THEN ' GENERATED ALWAYS AS IDENTITY'
+   WHEN 'd' THEN ' GENERATED BY DEFAULT AS IDENTITY'
+   ELSE ''
+  END,
+  CASE
+   WHEN a.attgenerated = 's' THEN format(' GENERATED ALWAYS AS (%s) STORED', pg_get_expr(ad.adbin, ad.adrelid))
+   WHEN ad.adbin IS NOT NULL THEN ' DEFAULT ' || pg_get_expr(ad.adbin, ad.adrelid)
+   ELSE ''
+  END,
+  CASE WHEN a.attnotnull THEN ' NOT NULL' ELSE '' END
+ ),
+ E',\\n    ' ORDER BY a.attnum
+),
+CASE WHEN c.relkind = 'p' THEN ' PARTITION BY ' || pg_get_partkeydef(c.oid) ELSE '' END)
+FROM pg_class c
+JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+LEFT JOIN pg_attrdef ad ON ad.adrelid = a.attrelid AND ad.adnum = a.attnum
+WHERE c.oid IN ({TABLES})
+GROUP BY c.oid, c.relname, c.relkind
+ORDER BY c.relname"
+            ),
+        )
+        .await
+        .with_context(|| "unable to fetch table definitions")?,
+    );
+
+    statements.extend(
+        fetch_statements(
+            pool,
+            tables,
+            &format!(
+                "
+SELECT format('CREATE TABLE %I PARTITION OF %I %s%s;',
+ c.relname,
+ parent.relname,
+ pg_get_expr(c.relpartbound, c.oid),
+ CASE WHEN c.relkind = 'p' THEN ' PARTITION BY ' || pg_get_partkeydef(c.oid) ELSE '' END
+)
+FROM pg_class root
+CROSS JOIN LATERAL pg_partition_tree(root.oid) t
+JOIN pg_class c ON c.oid = t.relid
+JOIN pg_class parent ON parent.oid = t.parentrelid
+WHERE root.oid IN ({TABLES})
+AND t.level > 0
+ORDER BY t.level, c.relname"
+            ),
+        )
+        .await
+        .with_context(|| "unable to fetch partitions")?,
+    );
+
+    statements.extend(
+        fetch_statements(
+            pool,
+            tables,
+            &format!("SELECT format('ALTER SEQUENCE %I OWNED BY %I.%I;', s.relname, t.relname, a.attname) {sequences}"),
+        )
+        .await
+        .with_context(|| "unable to fetch sequence ownerships")?,
+    );
+
+    statements.extend(
+        fetch_statements(
+            pool,
+            tables,
+            &format!(
+                "
+SELECT format('ALTER TABLE %I ADD CONSTRAINT %I %s;', t.relname, c.conname, pg_get_constraintdef(c.oid))
+FROM pg_constraint c
+JOIN pg_class t ON t.oid = c.conrelid
+WHERE t.oid IN ({TABLES})
+AND c.contype IN ('p', 'u', 'x', 'c', 'f')
+AND (c.contype <> 'f' OR c.confrelid IN ({TABLES}))
+ORDER BY c.contype = 'f', array_position(ARRAY['p', 'u', 'x', 'c']::\"char\"[], c.contype), t.relname, c.conname"
+            ),
+        )
+        .await
+        .with_context(|| "unable to fetch constraints")?,
+    );
+
+    statements.extend(
+        fetch_statements(
+            pool,
+            tables,
+            &format!(
+                "
+SELECT replace(pg_get_indexdef(i.indexrelid), ' ON ONLY ', ' ON ') || ';'
+FROM pg_index i
+JOIN pg_class idx ON idx.oid = i.indexrelid
+WHERE i.indrelid IN ({TABLES})
+AND NOT EXISTS (
+ SELECT 1 FROM pg_constraint c
+ WHERE c.conindid = i.indexrelid
+ AND c.contype IN ('p', 'u', 'x')
+)
+ORDER BY idx.relname"
+            ),
+        )
+        .await
+        .with_context(|| "unable to fetch indexes")?,
+    );
+
+    Ok(statements.join("\n\n") + "\n")
+}
+
+async fn fetch_statements(
+    pool: &Pool<Postgres>,
+    tables: &[String],
+    query: &str,
+) -> Result<Vec<String>> {
+    let statements = sqlx::query_as::<_, (String,)>(query)
+        .bind(tables)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(statements.into_iter().map(|(s,)| s).collect())
+}
+
+pub async fn write(pool: &Pool<Postgres>, tables: &[String], dir: &Path) -> Result<()> {
+    let path = dir.join(DDL_FILE);
+    let ddl = generate(pool, tables).await?;
+    tokio::fs::write(&path, ddl)
+        .await
+        .with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(())
+}
+
+pub async fn apply(transaction: &mut sqlx::Transaction<'_, Postgres>, dir: &Path) -> Result<()> {
+    let path = dir.join(DDL_FILE);
+    let ddl = tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("unable to read {}", path.display()))?;
+
+    transaction
+        .execute(ddl.as_str())
+        .await
+        .with_context(|| format!("unable to apply {}", path.display()))?;
+    Ok(())
+}
@@ -1,7 +1,17 @@
 pub mod config;
+pub mod connection;
+pub mod progress;
 pub mod run;
 
+mod audit;
+mod csv;
+mod ddl;
 mod graph;
+mod manifest;
+mod scan;
 mod sql;
 
 mod transform;
+mod validate;
+
+pub use transform::{CustomTransform, Row, TransformRegistry};
@@ -1,23 +1,97 @@
-use anyhow::Result;
-use clap::{ArgEnum, Parser};
-use pgsubset::config::Config;
-use sqlx::postgres::PgPoolOptions;
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
+use pgsubset::config::{parse_override, Config};
+use pgsubset::connection::{connect, host};
+use pgsubset::progress::{self, LogFormat};
 
+use std::io::{self, Write};
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[clap(version, about)]
 struct Args {
-    #[clap(short, long)]
-    config: PathBuf,
-    #[clap(arg_enum, short, long)]
-    mode: Mode,
+    #[clap(subcommand)]
+    command: Command,
+    /// Only report errors
+    #[clap(short, long, global = true)]
+    quiet: bool,
+    /// Format of the progress and events reported on stderr
+    #[clap(long, global = true, default_value = "text", possible_values = ["text", "json"])]
+    log_format: LogFormat,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Export a subset of the database to csv files
+    Export(Options),
+    /// Import an exported subset into the database
+    Import {
+        #[clap(flatten)]
+        options: Options,
+        /// Skip the confirmation prompt when cleaning a non-local database
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Print the queries an export would run
+    Plan(Options),
+    /// List the exported columns which likely contain personal data
+    Scan(Options),
+    /// Check the tables, columns and constraints named in the config exist
+    Validate(Options),
 }
 
-#[derive(ArgEnum, Clone)]
 enum Mode {
     Export,
     Import,
+    Plan,
+    Scan,
+    Validate,
+}
+
+#[derive(clap::Args)]
+struct Options {
+    #[clap(short, long)]
+    config: Option<PathBuf>,
+    /// Profile of the config to apply
+    #[clap(short, long)]
+    profile: Option<String>,
+    #[clap(long)]
+    database_url: Option<String>,
+    #[clap(long)]
+    target_table: Option<String>,
+    /// Condition selecting the exported rows of the target table
+    #[clap(long = "where")]
+    target_filter: Option<String>,
+    #[clap(long)]
+    target_dir: Option<String>,
+    /// Override a config value, e.g. transforms.user.email=email_en
+    #[clap(long = "set", value_name = "KEY=VALUE", parse(try_from_str = parse_override))]
+    overrides: Vec<toml::Value>,
+}
+
+impl Options {
+    /// `connection` is the block `--database-url` applies to
+    fn load(self, connection: &str) -> Result<Config> {
+        let mut overrides = toml::value::Table::new();
+        if let Some(url) = self.database_url {
+            let block = toml::value::Table::from_iter([("url".to_owned(), url.into())]);
+            overrides.insert(connection.to_owned(), block.into());
+        }
+        let flags = [
+            ("target_table", self.target_table),
+            ("target_filter", self.target_filter),
+            ("target_dir", self.target_dir),
+        ];
+        for (key, value) in flags {
+            if let Some(value) = value {
+                overrides.insert(key.to_owned(), value.into());
+            }
+        }
+
+        let mut all = self.overrides;
+        all.push(overrides.into());
+        Config::load(self.config.as_deref(), self.profile.as_deref(), all)
+    }
 }
 
 #[tokio::main]
@@ -25,17 +99,54 @@ async fn main() -> Result<()> {
     env_logger::init();
 
     let args = Args::parse();
-    let cfg_content = tokio::fs::read_to_string(args.config).await?;
-    let cfg: Config = toml::from_str(&cfg_content)?;
+    progress::init(args.log_format, args.quiet);
+    let (mode, options, yes) = match args.command {
+        Command::Export(options) => (Mode::Export, options, false),
+        Command::Import { options, yes } => (Mode::Import, options, yes),
+        Command::Plan(options) => (Mode::Plan, options, false),
+        Command::Scan(options) => (Mode::Scan, options, false),
+        Command::Validate(options) => (Mode::Validate, options, false),
+    };
+    let connection = match mode {
+        Mode::Import => "destination",
+        _ => "source",
+    };
+    let cfg = options.load(connection)?;
 
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&cfg.database_url)
-        .await?;
+    let pool = if let Mode::Import = mode {
+        let destination = cfg.destination();
+        let cleans = cfg.import.as_ref().and_then(|i| i.clean).is_some();
+        let host = host(&destination)?;
+        if cleans && !yes && !is_local(&host) {
+            confirm_clean(&host)?;
+        }
+        connect(&destination).await?
+    } else {
+        connect(&cfg.source()).await?
+    };
 
-    match args.mode {
+    match mode {
         Mode::Export => pgsubset::run::export(&pool, cfg).await?,
         Mode::Import => pgsubset::run::import(&pool, cfg).await?,
+        Mode::Plan => pgsubset::run::plan(&pool, cfg).await?,
+        Mode::Scan => pgsubset::run::scan(&pool, cfg).await?,
+        Mode::Validate => pgsubset::run::validate(&pool, cfg).await?,
+    }
+    Ok(())
+}
+
+fn is_local(host: &str) -> bool {
+    matches!(host, "" | "localhost" | "127.0.0.1" | "[::1]") || host.starts_with('/')
+}
+
+fn confirm_clean(host: &str) -> Result<()> {
+    print!("import will remove existing data from {host}, continue? [y/N] ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        bail!("import aborted");
     }
     Ok(())
 }
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ImportOptions;
+use crate::sql::Column;
+
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Manifest {
+    pub tables: Vec<TableEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TableEntry {
+    pub file: String,
+    pub table: String,
+    pub columns: Vec<ColumnEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ColumnEntry {
+    pub name: String,
+    pub data_type: String,
+}
+
+impl From<&Column> for ColumnEntry {
+    fn from(column: &Column) -> Self {
+        Self {
+            name: column.name.clone(),
+            data_type: column.data_type.clone(),
+        }
+    }
+}
+
+impl Manifest {
+    pub async fn read(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("unable to read {}", path.display()))?;
+        let manifest = toml::from_str(&content)
+            .with_context(|| format!("unable to parse {}", path.display()))?;
+        Ok(Some(manifest))
+    }
+
+    pub async fn write(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(MANIFEST_FILE);
+        let content = toml::to_string(self).with_context(|| "unable to serialize manifest")?;
+        tokio::fs::write(&path, content)
+            .await
+            .with_context(|| format!("unable to write {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn incompatibilities(
+        &self,
+        destination: &HashMap<String, Vec<Column>>,
+        options: &ImportOptions,
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        for entry in &self.tables {
+            let columns = match destination.get(&entry.table) {
+                Some(columns) => columns,
+                None => {
+                    problems.push(format!("table {} does not exist", entry.table));
+                    continue;
+                }
+            };
+
+            for exported in &entry.columns {
+                match columns.iter().find(|c| c.name == exported.name) {
+                    Some(column) if column.data_type != exported.data_type => {
+                        problems.push(format!(
+                            "column {}.{} has type {} but {} was exported",
+                            entry.table, exported.name, column.data_type, exported.data_type
+                        ))
+                    }
+                    Some(_) => {}
+                    None if options.ignore_extra_columns => {}
+                    None => problems.push(format!(
+                        "column {}.{} does not exist",
+                        entry.table, exported.name
+                    )),
+                }
+            }
+
+            for column in columns {
+                if column.generated || entry.columns.iter().any(|c| c.name == column.name) {
+                    continue;
+                }
+                if column.not_null && !column.has_default {
+                    problems.push(format!(
+                        "column {}.{} is missing from the export and has no default",
+                        entry.table, column.name
+                    ));
+                } else if !options.fill_missing_columns {
+                    problems.push(format!(
+                        "column {}.{} is missing from the export",
+                        entry.table, column.name
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
+    pub fn extra_columns(
+        &self,
+        file: &str,
+        destination: &HashMap<String, Vec<Column>>,
+    ) -> Vec<String> {
+        self.tables
+            .iter()
+            .filter(|entry| entry.file == file)
+            .flat_map(|entry| {
+                let columns = destination.get(&entry.table);
+                entry.columns.iter().filter(move |exported| {
+                    columns
+                        .map(|columns| columns.iter().all(|c| c.name != exported.name))
+                        .unwrap_or(true)
+                })
+            })
+            .map(|exported| exported.name.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::bail;
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, ReadBuf};
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("unknown log format {s}"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Settings {
+    format: LogFormat,
+    quiet: bool,
+}
+
+/// sets how events are reported, text by default; only the first call has an effect
+pub fn init(format: LogFormat, quiet: bool) {
+    let _ = SETTINGS.set(Settings { format, quiet });
+}
+
+/// reports an event on stderr, as `message` or as a json line with `fields`
+pub fn event(name: &str, message: impl fmt::Display, fields: Value) {
+    let settings = SETTINGS.get_or_init(Settings::default);
+    if settings.quiet {
+        return;
+    }
+    match settings.format {
+        LogFormat::Text => eprintln!("{message}"),
+        LogFormat::Json => {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or_default();
+            eprintln!("{}", json_line(name, timestamp, fields));
+        }
+    }
+}
+
+/// the json line of an event: its name and timestamp followed by `fields`, in order
+pub fn json_line(name: &str, timestamp: f64, fields: Value) -> String {
+    let mut line = json!({ "event": name, "timestamp": timestamp });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    line.to_string()
+}
+
+/// rows and bytes copied for a table, reported every few seconds with the throughput and,
+/// given an estimate of the total rows or bytes, the remaining time
+pub struct TableProgress {
+    action: &'static str,
+    table: String,
+    estimated_rows: Option<u64>,
+    total_bytes: Option<u64>,
+    rows: u64,
+    bytes: u64,
+    started: Instant,
+    reported: Instant,
+}
+
+impl TableProgress {
+    pub fn new(action: &'static str, table: &str) -> Self {
+        let now = Instant::now();
+        Self {
+            action,
+            table: table.to_owned(),
+            estimated_rows: None,
+            total_bytes: None,
+            rows: 0,
+            bytes: 0,
+            started: now,
+            reported: now,
+        }
+    }
+
+    pub fn estimated_rows(mut self, rows: Option<u64>) -> Self {
+        self.estimated_rows = rows;
+        self
+    }
+
+    pub fn total_bytes(mut self, bytes: Option<u64>) -> Self {
+        self.total_bytes = bytes;
+        self
+    }
+
+    pub fn add(&mut self, rows: u64, bytes: u64) {
+        self.rows += rows;
+        self.bytes += bytes;
+        if self.reported.elapsed() >= REPORT_INTERVAL {
+            self.reported = Instant::now();
+            self.report();
+        }
+    }
+
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    fn report(&self) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rows_per_sec = self.rows as f64 / elapsed;
+        // bytes are the better measure when their total is known
+        let eta = match (self.total_bytes, self.estimated_rows) {
+            (Some(total), _) if self.bytes > 0 => {
+                Some(total.saturating_sub(self.bytes) as f64 * elapsed / self.bytes as f64)
+            }
+            (_, Some(total)) if self.rows > 0 => {
+                Some(total.saturating_sub(self.rows) as f64 / rows_per_sec)
+            }
+            _ => None,
+        };
+
+        let mut message = format!("{} {}: {} rows", self.action, self.table, self.rows);
+        if let Some(total) = self.estimated_rows {
+            message += &format!(" of ~{total}");
+        }
+        message += &format!(", {}, {rows_per_sec:.0} rows/s", Bytes(self.bytes));
+        if let Some(eta) = eta {
+            message += &format!(", eta {eta:.0}s");
+        }
+        event(
+            "table_progress",
+            message,
+            json!({
+                "action": self.action,
+                "table": self.table,
+                "rows": self.rows,
+                "bytes": self.bytes,
+                "estimated_rows": self.estimated_rows,
+                "total_bytes": self.total_bytes,
+                "rows_per_sec": rows_per_sec,
+                "eta_secs": eta,
+            }),
+        );
+    }
+
+    pub fn finish(self, message: impl fmt::Display) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let text = format!(
+            "{message}: {} rows, {} in {elapsed:.1}s",
+            self.rows,
+            Bytes(self.bytes)
+        );
+        event(
+            "table_finished",
+            text,
+            json!({
+                "action": self.action,
+                "table": self.table,
+                "message": message.to_string(),
+                "rows": self.rows,
+                "bytes": self.bytes,
+                "elapsed_secs": elapsed,
+            }),
+        );
+    }
+}
+
+/// a reader reporting the progress of the csv read through it, one row per line
+pub struct ProgressReader<R> {
+    inner: R,
+    pub progress: TableProgress,
+}
+
+impl<R> ProgressReader<R> {
+    pub fn new(inner: R, progress: TableProgress) -> Self {
+        Self { inner, progress }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ProgressReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = &buf.filled()[before..];
+        let lines = read.iter().filter(|b| **b == b'\n').count() as u64;
+        let bytes = read.len() as u64;
+        self.progress.add(lines, bytes);
+        poll
+    }
+}
+
+struct Bytes(u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            b if b >= 1 << 30 => write!(f, "{:.1} GiB", b as f64 / (1u64 << 30) as f64),
+            b if b >= 1 << 20 => write!(f, "{:.1} MiB", b as f64 / (1u64 << 20) as f64),
+            b if b >= 1 << 10 => write!(f, "{:.1} KiB", b as f64 / (1u64 << 10) as f64),
+            b => write!(f, "{b} B"),
+        }
+    }
+}
@@ -1,55 +1,155 @@
-use crate::config::Config;
+use crate::audit::{self, Audit, ColumnAudit, TableAudit};
+use crate::config::{CleanMode, Config, ParameterisedTransform, TransformConfig};
+use crate::csv;
+use crate::ddl;
 use crate::graph::{relationships_as_edges, tables_as_nodes, DepGraph};
-use crate::sql::{build_queries, get_all_tables, get_relationships, ImportCmd};
-use crate::transform::{IndexedTransforms, TableTransform, Transform, Transforms};
-use anyhow::{anyhow, Context, Result};
+use crate::manifest::{ColumnEntry, Manifest, TableEntry};
+use crate::progress::{self, ProgressReader, TableProgress};
+use crate::scan;
+use crate::sql::{
+    build_queries, estimate_rows, get_all_tables, get_columns, get_generic_relationships,
+    get_partitions, get_primary_key, get_relationships, truncate_tables, Column, CopyCmd,
+    ImportCmd, KeyRemapper, Relationship,
+};
+use crate::transform::{
+    check_column, column_rng, split_json_path, IndexedTransforms, TableTransform, Transform,
+    TransformRegistry, Transforms,
+};
+use crate::validate::Schema;
+use anyhow::{anyhow, bail, Context, Result};
 use futures::future::{join_all, OptionFuture};
 use futures::stream::TryStreamExt;
 use regex::Regex;
+use serde_json::json;
 use sqlx::{Pool, Postgres};
-use std::borrow::Cow;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs::{self, File};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
 pub async fn export(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
-    tokio::fs::create_dir_all(&cfg.target_dir).await?;
+    export_with(pool, cfg, &TransformRegistry::new()).await
+}
 
-    let relationships = get_relationships(pool).await?;
-    let tables = get_all_tables(pool).await?;
-    let m2m_tables = Arc::new(cfg.m2m_tables.unwrap_or_default());
+/// same as `export`, with custom transforms available to the config
+pub async fn export_with(
+    pool: &Pool<Postgres>,
+    mut cfg: Config,
+    registry: &TransformRegistry,
+) -> Result<()> {
+    let target_table = cfg.target_table()?.to_owned();
+    tokio::fs::create_dir_all(&cfg.target_dir).await?;
 
-    let transforms = cfg
+    let mut audit = Audit::new(&cfg, Vec::new())?;
+    let audit_descriptions = cfg
         .transforms
-        .map(|transforms| {
-            transforms
-                .into_iter()
-                .map(|(key, val)| {
-                    let table_transforms = val
-                        .into_iter()
-                        .map(|(k, v)| (k, Transform::new(&v)))
-                        .collect::<TableTransform>();
-
-                    (key, table_transforms)
-                })
-                .collect::<Transforms>()
-        })
+        .as_ref()
+        .map(audit::describe_transforms)
         .unwrap_or_default();
-    let transforms = Arc::new(transforms);
+    let columns = get_columns(pool).await?;
+    check_config(pool, &cfg, &columns).await?;
 
-    let nodes = tables_as_nodes(&tables);
-    let edges = relationships_as_edges(&relationships, &m2m_tables);
-    let graph = DepGraph::new(nodes, edges)?;
-    let sorted_dependencies = graph.get_dependencies_of(cfg.target_table.as_str())?;
+    let subset = Subset::new(pool, &cfg).await?;
+    let options = cfg.export.take().unwrap_or_default();
+    if options.fail_on_pii {
+        let unmasked: Vec<_> = scan::scan(pool, &subset.tables, &cfg, &subset.nullified_columns)
+            .await?
+            .into_iter()
+            .filter(|finding| finding.coverage != scan::Coverage::Masked)
+            .map(|finding| finding.to_string())
+            .collect();
+        if !unmasked.is_empty() {
+            bail!("export would write unmasked pii:\n{}", unmasked.join("\n"));
+        }
+    }
+    let m2m_tables = cfg.m2m_tables.unwrap_or_default();
+
+    // sql transforms are pushed down to the copy query instead of being applied to the csv
+    let mut sql_transforms = HashMap::new();
+    let mut transforms = Transforms::new();
+    let seed = options.seed.as_deref();
+    for (table, configs) in cfg.transforms.unwrap_or_default() {
+        let table_columns = columns.get(&table).map(Vec::as_slice).unwrap_or_default();
+        let (table_transforms, expressions) =
+            table_transforms(&table, configs, table_columns, registry, seed)?;
+        transforms.insert(table.clone(), table_transforms);
+        sql_transforms.insert(table, expressions);
+    }
+    let transforms = Arc::new(transforms);
 
     let target_path = Arc::new(PathBuf::from(&cfg.target_dir));
 
-    let queries = build_queries(&cfg.target_table, &relationships, &sorted_dependencies);
+    let target_filter = cfg.target_filter.as_deref();
+    let partitions = options.partitions.as_ref();
+    let mut queries = subset
+        .queries(
+            pool,
+            &target_table,
+            target_filter,
+            &columns,
+            &sql_transforms,
+            partitions,
+        )
+        .await?;
     let cmd_len = queries.len();
+    let csv_names = queries
+        .iter()
+        .enumerate()
+        .map(|(idx, q)| {
+            let mut csv_path_index = idx;
+            if m2m_tables.iter().any(|x| x.name == q.table) {
+                csv_path_index += cmd_len;
+            }
+            format!("{csv_path_index:02}-{}.csv", q.table)
+        })
+        .collect::<Vec<_>>();
+
+    let manifest = Manifest {
+        tables: queries
+            .iter()
+            .zip(&csv_names)
+            .map(|(q, file)| TableEntry {
+                file: file.clone(),
+                table: q.table.clone(),
+                columns: columns
+                    .get(&q.table)
+                    .map(|columns| {
+                        columns
+                            .iter()
+                            .filter(|c| !c.generated)
+                            .map(ColumnEntry::from)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+            .collect(),
+    };
+    manifest.write(&target_path).await?;
+
+    if options.ddl {
+        let mut tables = manifest
+            .tables
+            .iter()
+            .map(|entry| entry.table.clone())
+            .collect::<Vec<_>>();
+        tables.sort();
+        tables.dedup();
+        ddl::write(pool, &tables, &target_path).await?;
+    }
+
     let mut handles = Vec::with_capacity(cmd_len);
 
-    for (idx, q) in queries.into_iter().enumerate() {
+    let mut estimates = Vec::with_capacity(cmd_len);
+    for q in queries.iter_mut() {
+        estimates.push(estimate_rows(pool, &q.table).await?);
+        // random transforms draw values in row order, so a seeded export has to read rows in a stable one
+        if seed.is_some() {
+            q.order_by = get_primary_key(pool, &q.table).await?;
+        }
+    }
+
+    for ((q, csv_path), estimate) in queries.into_iter().zip(csv_names).zip(estimates) {
         let mut conn = pool
             .clone()
             .acquire()
@@ -57,19 +157,14 @@ pub async fn export(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
             .with_context(|| "unable to acquire connection to database")?;
 
         let path = Arc::clone(&target_path);
-        let m2m = Arc::clone(&m2m_tables);
         let trans = Arc::clone(&transforms);
 
         handles.push(tokio::task::spawn(async move {
             let table_name = q.table.as_str();
             let ts = trans.get(table_name);
 
-            let mut csv_path_index = idx;
-            if m2m.iter().any(|x| x.name == table_name) {
-                csv_path_index += cmd_len;
-            }
-            let csv_path = format!("{csv_path_index:02}-{table_name}.csv");
             let full_path = path.join(csv_path.as_str());
+            let mut progress = TableProgress::new("export", table_name).estimated_rows(estimate);
 
             let mut data = conn
                 .copy_out_raw(q.build_query().as_str())
@@ -90,55 +185,375 @@ pub async fn export(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
                     let s = std::str::from_utf8(&c)
                         .map_err(|err| anyhow!("error decoding headers for {table_name}: {err}"))?;
 
-                    let header: Vec<_> = s.split(',').map(|s| s.to_owned()).collect();
-                    Ok::<_, anyhow::Error>(IndexedTransforms::new(t, &header))
+                    let header: Vec<_> = csv::parse_row(s)
+                        .into_iter()
+                        .map(Option::unwrap_or_default)
+                        .collect();
+                    Ok::<_, anyhow::Error>(IndexedTransforms::new(t, header))
                 })
                 .into();
 
-            let ts_with_idx = ts_with_idx.await.transpose()?.unwrap_or_default();
+            let mut ts_with_idx = ts_with_idx.await.transpose()?.unwrap_or_default();
+            // the header has already been read when the table has transforms
+            let mut header = ts.is_none();
 
             while let Some(chunk) = data.try_next().await? {
-                if !ts_with_idx.transforms.is_empty() {
+                let bytes = if !ts_with_idx.is_empty() {
                     let components = std::str::from_utf8(&chunk)
                         .map_err(|err| anyhow!("error decoding row for {table_name}: {err}"))?;
 
-                    let mut iter = components.split(',').enumerate().map(|(index, component)| {
-                        ts_with_idx
-                            .transforms
-                            .get(&index)
-                            .map(|f| Cow::Owned((f.func)(component)))
-                            .unwrap_or(Cow::Borrowed(component))
-                    });
-
-                    if let Some(s) = iter.next() {
-                        file.write_all(s.as_bytes()).await?;
-
-                        for s in iter {
-                            file.write_all(b",").await?;
-                            file.write_all(s.as_bytes()).await?;
-                        }
-                    }
+                    let mut fields = csv::parse_row(components);
+                    ts_with_idx.apply(&mut fields);
+                    let row = csv::write_row(&fields);
+                    file.write_all(row.as_bytes()).await?;
+                    row.len()
                 } else {
                     file.write_all(&chunk).await?;
+                    chunk.len()
+                };
+                if header {
+                    header = false;
+                } else {
+                    progress.add(1, bytes as u64);
                 }
             }
-            println!("{} writed", full_path.display());
-            Ok::<_, anyhow::Error>(())
+            let rows = progress.rows();
+            progress.finish(format_args!("exported {}", full_path.display()));
+            Ok::<_, anyhow::Error>((csv_path, rows, ts_with_idx.affected_rows()))
         }));
     }
 
-    join_all(handles)
-        .await
-        .into_iter()
+    let mut results = HashMap::new();
+    for result in join_all(handles).await {
+        let (file, rows, affected_rows) =
+            result.with_context(|| "something went wrong fetching task handles")??;
+        results.insert(file, (rows, affected_rows));
+    }
+
+    for entry in manifest.tables {
+        let (rows, affected_rows) = results
+            .remove(&entry.file)
+            .with_context(|| format!("no export result for {}", entry.file))?;
+        let descriptions = audit_descriptions.get(&entry.table);
+        let nullified = subset.nullified_columns.get(&entry.table);
+        let sql = sql_transforms.get(&entry.table);
+        let columns = entry
+            .columns
+            .into_iter()
+            .map(|column| {
+                let mut transform = descriptions.and_then(|d| d.get(&column.name)).cloned();
+                // nullified and sql columns are written by postgres for every row
+                let mut affected = affected_rows.get(&column.name).copied();
+                if nullified.is_some_and(|n| n.contains(&column.name)) {
+                    transform = Some("set_null (ignored relationship)".to_owned());
+                    affected = Some(rows);
+                } else if sql.is_some_and(|s| s.contains_key(&column.name)) {
+                    affected = Some(rows);
+                }
+                ColumnAudit {
+                    name: column.name,
+                    affected_rows: transform.as_ref().and(affected),
+                    transform,
+                }
+            })
+            .collect();
+        audit.tables.push(TableAudit {
+            file: entry.file,
+            table: entry.table,
+            rows,
+            columns,
+        });
+    }
+    audit.write(&target_path).await?;
+    Ok(())
+}
+
+/// splits the transforms of a table into the ones applied to the csv and sql expressions
+fn table_transforms(
+    table: &str,
+    configs: HashMap<String, TransformConfig>,
+    columns: &[Column],
+    registry: &TransformRegistry,
+    seed: Option<&str>,
+) -> Result<(TableTransform, HashMap<String, String>)> {
+    let mut transforms = TableTransform::new();
+    let mut expressions = HashMap::new();
+    let mut json_paths: HashMap<String, Vec<(Vec<String>, Transform)>> = HashMap::new();
+
+    for (key, config) in configs {
+        let context = || format!("invalid transform for {table}.{key}");
+        let rng = column_rng(seed, table, &key);
+        let (column_name, path) = match split_json_path(&key) {
+            Some((column, path)) => (column.to_owned(), Some(path)),
+            None => (key.clone(), None),
+        };
+        if let Some(column) = columns.iter().find(|c| c.name == column_name) {
+            if path.is_none() {
+                check_column(&config, column).with_context(context)?;
+            } else if !column.data_type.starts_with("json") {
+                bail!(
+                    "{}: json paths can't be applied to {}",
+                    context(),
+                    column.data_type
+                );
+            }
+        }
+
+        match (path, config) {
+            (None, TransformConfig::Parameterised(ParameterisedTransform::Sql { expression })) => {
+                expressions.insert(key, expression);
+            }
+            (None, config) => {
+                let transform = Transform::new(&config, registry, rng).with_context(context)?;
+                transforms.insert(key, transform);
+            }
+            (Some(path), config) => {
+                let transform = Transform::new(&config, registry, rng).with_context(context)?;
+                json_paths
+                    .entry(column_name)
+                    .or_default()
+                    .push((path, transform));
+            }
+        }
+    }
+
+    for (column, paths) in json_paths {
+        if transforms.contains_key(&column) || expressions.contains_key(&column) {
+            bail!("{table}.{column} has both a transform and json path transforms");
+        }
+        transforms.insert(column, Transform::json_paths(paths));
+    }
+    Ok((transforms, expressions))
+}
+
+/// checks the tables, columns and constraints named in the config exist and the transforms
+/// can be applied to their columns
+pub async fn validate(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
+    validate_with(pool, cfg, &TransformRegistry::new()).await
+}
+
+/// same as `validate`, with custom transforms available to the config
+pub async fn validate_with(
+    pool: &Pool<Postgres>,
+    cfg: Config,
+    registry: &TransformRegistry,
+) -> Result<()> {
+    let columns = get_columns(pool).await?;
+    check_config(pool, &cfg, &columns).await?;
+    for (table, configs) in cfg.transforms.unwrap_or_default() {
+        let table_columns = columns.get(&table).map(Vec::as_slice).unwrap_or_default();
+        table_transforms(&table, configs, table_columns, registry, None)?;
+    }
+    println!("config is valid");
+    Ok(())
+}
+
+async fn check_config(
+    pool: &Pool<Postgres>,
+    cfg: &Config,
+    columns: &HashMap<String, Vec<Column>>,
+) -> Result<()> {
+    cfg.target_table()?;
+    let relationships = get_relationships(pool).await?;
+    let schema = Schema {
+        columns,
+        constraints: relationships
+            .iter()
+            .filter_map(|rel| rel.constraint_name.as_deref())
+            .collect(),
+    };
+    let problems = schema.check(cfg);
+    if !problems.is_empty() {
+        bail!("invalid config:\n{}", problems.join("\n"));
+    }
+    Ok(())
+}
+
+/// prints the queries an export would run, without writing anything
+pub async fn plan(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
+    let columns = get_columns(pool).await?;
+    check_config(pool, &cfg, &columns).await?;
+
+    let subset = Subset::new(pool, &cfg).await?;
+    let sql_transforms = cfg
+        .transforms
+        .iter()
         .flatten()
-        .collect::<Result<Vec<()>, _>>()
-        .with_context(|| "something went wrong fetching task handles")?;
+        .map(|(table, transforms)| {
+            let expressions = transforms
+                .iter()
+                .filter_map(|(column, transform)| match transform {
+                    TransformConfig::Parameterised(ParameterisedTransform::Sql { expression }) => {
+                        Some((column.clone(), expression.clone()))
+                    }
+                    _ => None,
+                })
+                .collect();
+            (table.clone(), expressions)
+        })
+        .collect();
+    let partitions = cfg.export.as_ref().and_then(|e| e.partitions.as_ref());
+    let queries = subset
+        .queries(
+            pool,
+            cfg.target_table()?,
+            cfg.target_filter.as_deref(),
+            &columns,
+            &sql_transforms,
+            partitions,
+        )
+        .await?;
+
+    for q in queries {
+        println!("{}: {}", q.table, q.build_query());
+    }
     Ok(())
 }
 
-pub async fn import(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
+/// prints the likely pii columns of the subset and whether they are masked
+pub async fn scan(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
+    let subset = Subset::new(pool, &cfg).await?;
+    let findings = scan::scan(pool, &subset.tables, &cfg, &subset.nullified_columns).await?;
+    if findings.is_empty() {
+        println!("no likely pii found in exported columns");
+    }
+    for finding in findings {
+        println!("{finding}");
+    }
+    Ok(())
+}
+
+/// the tables exported for a config, in dependency order
+struct Subset {
+    relationships: Vec<Relationship>,
+    nullified_columns: HashMap<String, Vec<String>>,
+    tables: Vec<String>,
+}
+impl Subset {
+    async fn new(pool: &Pool<Postgres>, cfg: &Config) -> Result<Self> {
+        let (relationships, nullified_columns) = get_all_relationships(pool, cfg).await?;
+        let tables = get_all_tables(pool).await?;
+        let m2m_tables = cfg.m2m_tables.as_deref().unwrap_or_default();
+
+        let nodes = tables_as_nodes(&tables);
+        let edges = relationships_as_edges(&relationships, m2m_tables);
+        let graph = DepGraph::new(nodes, edges)?;
+        let tables = graph.get_dependencies_of(cfg.target_table()?)?;
+
+        Ok(Self {
+            relationships,
+            nullified_columns,
+            tables,
+        })
+    }
+
+    /// the copy queries of the subset, with nullified columns and sql transforms applied
+    async fn queries(
+        &self,
+        pool: &Pool<Postgres>,
+        target_table: &str,
+        target_filter: Option<&str>,
+        columns: &HashMap<String, Vec<Column>>,
+        sql_transforms: &HashMap<String, HashMap<String, String>>,
+        partitions: Option<&HashMap<String, Vec<String>>>,
+    ) -> Result<Vec<CopyCmd>> {
+        let mut queries = build_queries(
+            target_table,
+            target_filter,
+            &self.relationships,
+            &self.tables,
+        );
+        for q in queries.iter_mut() {
+            let nullified = self.nullified_columns.get(&q.table);
+            let expressions = sql_transforms.get(&q.table);
+            q.columns = columns
+                .get(&q.table)
+                .map(|columns| {
+                    columns
+                        .iter()
+                        .filter(|c| !c.generated)
+                        .map(|c| {
+                            if nullified.is_some_and(|nullified| nullified.contains(&c.name)) {
+                                format!("NULL AS {}", c.name)
+                            } else if let Some(expression) =
+                                expressions.and_then(|e| e.get(&c.name))
+                            {
+                                format!("({expression}) AS {}", c.name)
+                            } else {
+                                c.name.clone()
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            if let Some(selected) = partitions.and_then(|p| p.get(&q.table)) {
+                q.conditions
+                    .push(partitions_condition(pool, &q.table, selected).await?);
+            }
+        }
+        Ok(queries)
+    }
+}
+
+async fn get_all_relationships(
+    pool: &Pool<Postgres>,
+    cfg: &Config,
+) -> Result<(Vec<Relationship>, HashMap<String, Vec<String>>)> {
+    let mut relationships = get_relationships(pool).await?;
+    for rel in cfg.virtual_relationships.iter().flatten() {
+        relationships.push(Relationship::try_from(rel)?);
+    }
+
+    if let Some(generic_relationships) = &cfg.generic_relationships {
+        let tables = get_all_tables(pool).await?;
+        for rel in generic_relationships {
+            relationships.extend(get_generic_relationships(pool, rel, &tables).await?);
+        }
+    }
+
+    let ignored_relationships = cfg.ignored_relationships.as_deref().unwrap_or_default();
+
+    for ignored in ignored_relationships {
+        if ignored.constraint.is_some() == ignored.column.is_some() {
+            bail!("ignored relationships must specify exactly one of constraint or column");
+        }
+    }
+
+    let mut nullified_columns: HashMap<String, Vec<String>> = HashMap::new();
+    relationships.retain(|rel| {
+        let ignored = ignored_relationships.iter().find(|i| rel.matches(i));
+        if matches!(ignored, Some(i) if i.nullify) {
+            nullified_columns
+                .entry(rel.source_table.clone())
+                .or_default()
+                .extend(rel.source_columns.iter().cloned());
+        }
+        ignored.is_none()
+    });
+    Ok((relationships, nullified_columns))
+}
+
+async fn partitions_condition(
+    pool: &Pool<Postgres>,
+    table: &str,
+    selected: &[String],
+) -> Result<String> {
+    let available = get_partitions(pool, table).await?;
+    let mut leaves = Vec::new();
+    for partition in selected {
+        for leaf in get_partitions(pool, partition).await? {
+            if !available.contains(&leaf) {
+                bail!("{partition} is not a partition of {table}");
+            }
+            leaves.push(format!("'{}'::regclass", leaf.replace('\'', "''")));
+        }
+    }
+    Ok(format!("{table}.tableoid IN ({})", leaves.join(", ")))
+}
+
+pub async fn import(pool: &Pool<Postgres>, mut cfg: Config) -> Result<()> {
     let target_path = PathBuf::from(&cfg.target_dir);
-    let mut dir_entries = fs::read_dir(target_path)
+    let mut dir_entries = fs::read_dir(&target_path)
         .await
         .with_context(|| format!("error opening target_dir: {}", &cfg.target_dir))?;
 
@@ -155,16 +570,8 @@ pub async fn import(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
 
     let re = Regex::new(r"\d\d-(.*)\.csv$")?;
 
-    let mut header = String::new();
-
+    let mut tables = Vec::with_capacity(csvs.len());
     for csv in csvs {
-        let fd = File::open(&csv)
-            .await
-            .with_context(|| format!("error opening {}", csv.display()))?;
-        let mut reader = BufReader::new(fd);
-        header.clear();
-        reader.read_line(&mut header).await?;
-
         let csv_name = match csv.file_name() {
             Some(name) => name
                 .to_str()
@@ -173,11 +580,122 @@ pub async fn import(pool: &Pool<Postgres>, cfg: Config) -> Result<()> {
         };
         let table = re
             .captures(csv_name)
-            .with_context(|| format!("Unable to parse the csv filename {}", csv.display()))?;
+            .with_context(|| format!("Unable to parse the csv filename {}", csv.display()))?[1]
+            .to_owned();
+        tables.push((csv, table));
+    }
+
+    let options = cfg.import.take().unwrap_or_default();
+    let manifest = if options.apply_ddl {
+        None
+    } else {
+        Manifest::read(&target_path).await?
+    };
+    let columns = match &manifest {
+        Some(manifest) => {
+            let columns = get_columns(pool).await?;
+            let problems = manifest.incompatibilities(&columns, &options);
+            if !problems.is_empty() {
+                bail!(
+                    "the export is not compatible with the destination schema:\n  {}",
+                    problems.join("\n  ")
+                );
+            }
+            columns
+        }
+        None => HashMap::new(),
+    };
+    let ignored_columns = |csv: &Path| match (&manifest, csv.file_name()) {
+        (Some(manifest), Some(name)) if options.ignore_extra_columns => {
+            manifest.extra_columns(&name.to_string_lossy(), &columns)
+        }
+        _ => Vec::new(),
+    };
+
+    let mut transaction = pool.begin().await?;
 
-        let import_cmd = ImportCmd::new(&table[1], header.as_str());
-        import_cmd.import(pool, reader).await?;
-        println!("imported {} to {}", &csv.display(), import_cmd.table);
+    if options.apply_ddl {
+        ddl::apply(&mut transaction, &target_path).await?;
+        let path = target_path.join(ddl::DDL_FILE);
+        progress::event(
+            "ddl_applied",
+            format_args!("applied {}", path.display()),
+            json!({ "file": path }),
+        );
     }
+
+    match options.clean {
+        Some(CleanMode::Truncate) => {
+            let mut names: Vec<&str> = Vec::with_capacity(tables.len());
+            for (_, table) in tables.iter().rev() {
+                if !names.contains(&table.as_str()) {
+                    names.push(table);
+                }
+            }
+            truncate_tables(&mut transaction, &names).await?;
+            progress::event(
+                "truncated",
+                format_args!("truncated {}", names.join(", ")),
+                json!({ "tables": names }),
+            );
+        }
+        Some(CleanMode::Delete) => {
+            for (csv, table) in tables.iter().rev() {
+                let mut reader = open_progress_csv("delete", csv, table).await?;
+                ImportCmd::new(table, reader.header.clone())
+                    .ignoring(ignored_columns(csv))
+                    .delete_matching(&mut transaction, &mut reader.reader)
+                    .await?;
+                let message = format!("deleted rows matching {} from {table}", csv.display());
+                reader.reader.progress.finish(message);
+            }
+        }
+        None => {}
+    }
+
+    let relationships = if options.remap_keys {
+        get_all_relationships(pool, &cfg).await?.0
+    } else {
+        Vec::new()
+    };
+    let mut remapper = options.remap_keys.then(|| KeyRemapper::new(&relationships));
+
+    for (csv, table) in tables {
+        let mut reader = open_progress_csv("import", &csv, &table).await?;
+        let import_cmd = ImportCmd::new(table, reader.header).ignoring(ignored_columns(&csv));
+        import_cmd
+            .import(&mut transaction, &mut reader.reader, remapper.as_mut())
+            .await?;
+        let message = format!("imported {} to {}", csv.display(), import_cmd.table);
+        reader.reader.progress.finish(message);
+    }
+    transaction.commit().await?;
     Ok(())
 }
+
+struct ProgressCsv {
+    header: String,
+    reader: ProgressReader<BufReader<File>>,
+}
+
+/// opens a csv reporting the progress of reading its rows
+async fn open_progress_csv(action: &'static str, csv: &Path, table: &str) -> Result<ProgressCsv> {
+    let (header, reader) = open_csv(csv).await?;
+    let size = fs::metadata(csv).await.ok().map(|m| m.len());
+    let data_size = size.map(|size| size.saturating_sub(header.len() as u64));
+    let progress = TableProgress::new(action, table).total_bytes(data_size);
+    Ok(ProgressCsv {
+        header,
+        reader: ProgressReader::new(reader, progress),
+    })
+}
+
+async fn open_csv(csv: &Path) -> Result<(String, BufReader<File>)> {
+    let fd = File::open(csv)
+        .await
+        .with_context(|| format!("error opening {}", csv.display()))?;
+    let mut reader = BufReader::new(fd);
+    let mut header = String::new();
+    reader.read_line(&mut header).await?;
+    Ok((header, reader))
+}
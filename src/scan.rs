@@ -0,0 +1,186 @@
+use crate::config::{Config, ParameterisedTransform, TransformConfig};
+use crate::sql::{get_columns, sample_column};
+use crate::transform::is_textual;
+use anyhow::Result;
+use regex::Regex;
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use std::fmt;
+
+const SAMPLE_SIZE: i64 = 100;
+/// share of the sampled values which have to look like pii to flag a column
+const SAMPLE_THRESHOLD: f64 = 0.5;
+
+pub struct Finding {
+    pub table: String,
+    pub column: String,
+    pub kind: &'static str,
+    pub reason: String,
+    pub coverage: Coverage,
+}
+
+#[derive(PartialEq)]
+pub enum Coverage {
+    Masked,
+    /// only some rows are masked, by a conditional transform without `otherwise`
+    Partial,
+    Unmasked,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self.coverage {
+            Coverage::Masked => "masked",
+            Coverage::Partial => "PARTIALLY MASKED (conditional transform without otherwise)",
+            Coverage::Unmasked => "NOT MASKED",
+        };
+        write!(
+            f,
+            "{}.{}: {} ({}), {status}",
+            self.table, self.column, self.kind, self.reason
+        )
+    }
+}
+
+struct Detector {
+    kind: &'static str,
+    name: Regex,
+    value: Option<Regex>,
+}
+
+fn detectors() -> Result<Vec<Detector>> {
+    let detector = |kind, name: &str, value: Option<&str>| -> Result<Detector> {
+        Ok(Detector {
+            kind,
+            name: Regex::new(&format!("(?i){name}"))?,
+            value: value.map(Regex::new).transpose()?,
+        })
+    };
+
+    Ok(vec![
+        detector(
+            "email",
+            "e_?mail",
+            Some(r"^[^@\s]+@[^@\s]+\.[[:alpha:]]{2,}$"),
+        )?,
+        detector(
+            "phone number",
+            "phone|mobile|fax|^tel",
+            Some(r"^\+?[\d\s().-]{7,20}$"),
+        )?,
+        detector(
+            "iban",
+            "iban|account_number",
+            Some(r"^[A-Z]{2}\d{2}[A-Z0-9]{11,30}$"),
+        )?,
+        detector(
+            "ip address",
+            "(^|_)ip(_|$)|remote_addr",
+            Some(r"^(\d{1,3}(\.\d{1,3}){3}|[[:xdigit:]]*:[[:xdigit:]:]+)$"),
+        )?,
+        detector(
+            "token",
+            "token|secret|password|api_key",
+            Some(r"^[A-Za-z0-9_\-+/=]{32,}$"),
+        )?,
+        detector(
+            "name",
+            "first_?name|last_?name|surname|full_?name|^name$",
+            None,
+        )?,
+        detector("address", "address|street|zip_?code|postcode", None)?,
+        detector("birth date", "birth|^dob$", None)?,
+    ])
+}
+
+/// looks for likely pii in the exported columns, by column name and by sampling values
+pub async fn scan(
+    pool: &Pool<Postgres>,
+    tables: &[String],
+    cfg: &Config,
+    nullified_columns: &HashMap<String, Vec<String>>,
+) -> Result<Vec<Finding>> {
+    let detectors = detectors()?;
+    let columns = get_columns(pool).await?;
+    let mut findings = Vec::new();
+
+    for table in tables {
+        let transforms = cfg.transforms.as_ref().and_then(|t| t.get(table));
+        let nullified = nullified_columns.get(table);
+        for column in columns.get(table).into_iter().flatten() {
+            if column.generated {
+                continue;
+            }
+
+            let detected = match detectors.iter().find(|d| d.name.is_match(&column.name)) {
+                Some(detector) => Some((detector.kind, "column name".to_owned())),
+                None if is_textual(&column.data_type) => {
+                    let values = sample_column(pool, table, &column.name, SAMPLE_SIZE).await?;
+                    detectors
+                        .iter()
+                        .filter_map(|d| {
+                            let value = d.value.as_ref()?;
+                            let matching = values.iter().filter(|v| value.is_match(v)).count();
+                            Some((d.kind, matching))
+                        })
+                        .filter(|(_, matching)| {
+                            *matching > 0
+                                && *matching as f64 / values.len() as f64 >= SAMPLE_THRESHOLD
+                        })
+                        .max_by_key(|(_, matching)| *matching)
+                        .map(|(kind, matching)| {
+                            let pct = matching * 100 / values.len();
+                            (kind, format!("{pct}% of sampled values"))
+                        })
+                }
+                None => None,
+            };
+
+            if let Some((kind, reason)) = detected {
+                let path_prefix = format!("{}.$", column.name);
+                let configs: Vec<_> = transforms
+                    .into_iter()
+                    .flatten()
+                    .filter(|(key, _)| **key == column.name || key.starts_with(&path_prefix))
+                    .map(|(_, config)| config)
+                    .collect();
+                let coverage = if nullified.is_some_and(|n| n.contains(&column.name)) {
+                    Coverage::Masked
+                } else if configs.is_empty() {
+                    Coverage::Unmasked
+                } else if configs.into_iter().all(masks_every_row) {
+                    Coverage::Masked
+                } else {
+                    Coverage::Partial
+                };
+                findings.push(Finding {
+                    table: table.clone(),
+                    column: column.name.clone(),
+                    kind,
+                    reason,
+                    coverage,
+                });
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+/// whether the transform applies to every row, which a conditional without `otherwise` doesn't
+fn masks_every_row(config: &TransformConfig) -> bool {
+    match config {
+        TransformConfig::Parameterised(ParameterisedTransform::Conditional {
+            transform,
+            otherwise,
+            ..
+        }) => otherwise
+            .as_ref()
+            .is_some_and(|otherwise| masks_every_row(transform) && masks_every_row(otherwise)),
+        TransformConfig::Parameterised(
+            ParameterisedTransform::JsonKeys { transform, .. }
+            | ParameterisedTransform::ArrayElements { transform },
+        ) => masks_every_row(transform),
+        _ => true,
+    }
+}
@@ -1,79 +1,189 @@
-use anyhow::{Context, Result};
-use sqlx::{Pool, Postgres};
-use std::ops::Not;
+use crate::config::{Discriminator, GenericRelationship, IgnoredRelationship, VirtualRelationship};
+use anyhow::{bail, Context, Result};
+use sqlx::{Executor, Pool, Postgres};
+use std::collections::HashMap;
 use tokio::io::AsyncRead;
 
 type Sequence = (String, String);
 
 pub struct Relationship {
+    pub constraint_name: Option<String>,
     pub source_table: String,
     pub dest_table: String,
-    pub source_column: String,
-    pub dest_column: String,
+    pub source_columns: Vec<String>,
+    pub dest_columns: Vec<String>,
+    pub discriminator: Option<Discriminator>,
+}
+
+impl Relationship {
+    pub fn matches(&self, ignored: &IgnoredRelationship) -> bool {
+        if let Some(constraint) = &ignored.constraint {
+            return self.constraint_name.as_ref() == Some(constraint);
+        }
+
+        ignored
+            .column
+            .as_ref()
+            .and_then(|column| column.split_once('.'))
+            .map(|(table, column)| {
+                self.source_table == table && self.source_columns.iter().any(|c| c == column)
+            })
+            .unwrap_or(false)
+    }
+
+    fn dependants_clause(&self, source_filter: Option<&str>) -> String {
+        let dest_columns = self
+            .dest_columns
+            .iter()
+            .map(|column| format!("{}.{column}", self.dest_table))
+            .collect::<Vec<_>>();
+        let dest_columns = match dest_columns.as_slice() {
+            [column] => column.clone(),
+            columns => format!("({})", columns.join(", ")),
+        };
+        let discriminator = self
+            .discriminator
+            .as_ref()
+            .map(|d| format!("{} = {}", d.column, quote_literal(&d.value)));
+        let filter = match (discriminator, source_filter) {
+            (Some(discriminator), Some(source_filter)) => {
+                format!(" WHERE {discriminator} AND ({source_filter})")
+            }
+            (Some(filter), None) => format!(" WHERE {filter}"),
+            (None, Some(filter)) => format!(" WHERE {filter}"),
+            (None, None) => String::new(),
+        };
+
+        format!(
+            "{} IN (SELECT DISTINCT {} FROM {}{})",
+            dest_columns,
+            self.source_columns.join(", "),
+            self.source_table,
+            filter
+        )
+    }
+}
+
+impl TryFrom<&VirtualRelationship> for Relationship {
+    type Error = anyhow::Error;
+
+    fn try_from(rel: &VirtualRelationship) -> Result<Self> {
+        if rel.source_columns.is_empty() || rel.source_columns.len() != rel.dest_columns.len() {
+            bail!(
+                "virtual relationship from {} to {} must have the same non-zero number of source and dest columns",
+                rel.source_table,
+                rel.dest_table
+            );
+        }
+
+        Ok(Self {
+            constraint_name: None,
+            source_table: rel.source_table.clone(),
+            dest_table: rel.dest_table.clone(),
+            source_columns: rel.source_columns.clone(),
+            dest_columns: rel.dest_columns.clone(),
+            discriminator: rel.discriminator.clone(),
+        })
+    }
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+    pub not_null: bool,
+    pub has_default: bool,
+    pub generated: bool,
 }
 
 pub struct CopyCmd {
     pub table: String,
-    pub clauses: String,
+    pub columns: Vec<String>,
+    pub conditions: Vec<String>,
+    pub order_by: Vec<String>,
 }
 
 impl CopyCmd {
-    pub fn new(table: impl Into<String>, clauses: impl Into<String>) -> Self {
+    pub fn new(table: impl Into<String>, conditions: Vec<String>) -> Self {
         Self {
             table: table.into(),
-            clauses: clauses.into(),
+            columns: Vec::new(),
+            conditions,
+            order_by: Vec::new(),
         }
     }
 
     pub fn build_query(&self) -> String {
+        let clauses = match self.conditions.as_slice() {
+            [] => String::new(),
+            [condition] => format!("WHERE {condition}"),
+            conditions => format!("WHERE ({})", conditions.join(") AND (")),
+        };
+        let columns = if self.columns.is_empty() {
+            "*".to_owned()
+        } else {
+            self.columns.join(", ")
+        };
+        let order = if self.order_by.is_empty() {
+            String::new()
+        } else {
+            format!(" ORDER BY {}", self.order_by.join(", "))
+        };
         format!(
-            "COPY (SELECT * FROM {} {}) TO STDOUT CSV HEADER",
-            &self.table, &self.clauses
+            "COPY (SELECT {} FROM {} {}{}) TO STDOUT CSV HEADER",
+            columns, &self.table, clauses, order
         )
     }
 }
 
+/// when the target table is filtered, the rows of each table are restricted to the ones referenced
+/// by the exported rows of its dependants instead of by all their rows
 pub fn build_queries(
     target_table: &str,
+    target_filter: Option<&str>,
     relationships: &[Relationship],
     sorted_dependencies: &[String],
 ) -> Vec<CopyCmd> {
+    let mut filters: HashMap<&str, String> = HashMap::new();
+    if let Some(filter) = target_filter {
+        filters.insert(target_table, filter.to_owned());
+    }
+
+    // dependants come after the tables they reference
     let mut queries = sorted_dependencies
         .iter()
+        .rev()
         .map(|dep| {
-            let mut iter = relationships.iter().filter(|rel| {
-                rel.dest_table == *dep
-                    && sorted_dependencies.binary_search(&rel.source_table).is_ok()
-            });
-
-            let dependants = iter
-                .next()
+            let dependants = relationships
+                .iter()
+                .filter(|rel| {
+                    rel.dest_table == *dep && sorted_dependencies.contains(&rel.source_table)
+                })
                 .map(|rel| {
-                    let mut clause = format!(
-                        "WHERE {}.{} IN (SELECT DISTINCT {} FROM {})",
-                        rel.dest_table, rel.dest_column, rel.source_column, rel.source_table
-                    );
-
-                    clause = iter.fold(clause, |acc, rel| {
-                        acc + format!(
-                            " OR {}.{} IN (SELECT DISTINCT {} FROM {})",
-                            rel.dest_table, rel.dest_column, rel.source_column, rel.source_table
-                        )
-                        .as_str()
-                    });
-                    clause
+                    rel.dependants_clause(
+                        filters.get(rel.source_table.as_str()).map(String::as_str),
+                    )
                 })
-                .unwrap_or_default();
+                .reduce(|acc, clause| acc + " OR " + clause.as_str());
 
-            CopyCmd::new(dep, dependants)
+            if let Some(dependants) = &dependants {
+                if target_filter.is_some() && dep != target_table {
+                    filters.insert(dep, dependants.clone());
+                }
+            }
+            CopyCmd::new(dep, dependants.into_iter().collect())
         })
         .collect::<Vec<_>>();
+    queries.reverse();
 
-    if queries
-        .iter()
-        .any(|x| x.table == target_table && x.clauses.is_empty().not())
-    {
-        queries.push(CopyCmd::new(target_table, ""));
+    let target_filter: Vec<_> = target_filter.map(str::to_owned).into_iter().collect();
+    match queries.iter_mut().find(|x| x.table == target_table) {
+        Some(target) if target.conditions.is_empty() => target.conditions = target_filter,
+        Some(_) => queries.push(CopyCmd::new(target_table, target_filter)),
+        None => {}
     }
 
     queries
@@ -83,6 +193,7 @@ pub struct ImportCmd {
     pub table: String,
     temp_table: String,
     header: String,
+    ignored_columns: Vec<String>,
 }
 impl ImportCmd {
     pub fn new<T>(table_name: T, header: impl Into<String>) -> Self
@@ -94,30 +205,76 @@ impl ImportCmd {
             table: table_name.into(),
             temp_table,
             header: header.into(),
+            ignored_columns: Vec::new(),
         }
     }
 
+    pub fn ignoring(mut self, columns: Vec<String>) -> Self {
+        self.ignored_columns = columns;
+        self
+    }
+
     pub async fn import<T: AsyncRead + Unpin>(
         &self,
-        pool: &Pool<Postgres>,
+        transaction: &mut sqlx::Transaction<'_, Postgres>,
         reader: T,
+        remapper: Option<&mut KeyRemapper<'_>>,
     ) -> Result<()> {
-        let mut transaction = pool.begin().await?;
-        self.create_temp_table(&mut transaction).await?;
-        self.copy_csv(&mut transaction, reader).await?;
-        self.insert_to(&mut transaction).await?;
+        self.create_temp_table(transaction).await?;
+        self.copy_csv(transaction, reader).await?;
+        if let Some(remapper) = remapper {
+            remapper
+                .remap(transaction, self)
+                .await
+                .with_context(|| format!("unable to remap keys of {}", &self.table))?;
+        }
+        self.insert_to(transaction).await?;
 
         let sequences = self
-            .get_sequences(&mut transaction)
+            .get_sequences(transaction)
             .await
             .with_context(|| format!("unable to get sequences for {}", &self.table))?;
 
         for seq in sequences {
-            self.update_sequence(&mut transaction, &seq)
+            self.update_sequence(transaction, &seq)
                 .await
                 .with_context(|| format!("unable to update sequence {}", seq.0))?;
         }
-        transaction.commit().await?;
+        self.drop_temp_table(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn delete_matching<T: AsyncRead + Unpin>(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Postgres>,
+        reader: T,
+    ) -> Result<()> {
+        let keys = get_primary_key(&mut **transaction, &self.table).await?;
+        if keys.is_empty() {
+            bail!(
+                "unable to delete matching rows from {}: no primary key",
+                &self.table
+            );
+        }
+
+        self.create_temp_table(transaction).await?;
+        self.copy_csv(transaction, reader).await?;
+
+        let condition = keys
+            .iter()
+            .map(|key| format!("{}.{key} = {}.{key}", &self.table, &self.temp_table))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let query = format!(
+            "DELETE FROM {} USING {} WHERE {}",
+            &self.table, &self.temp_table, condition
+        );
+        sqlx::query(query.as_str())
+            .execute(&mut **transaction)
+            .await
+            .with_context(|| format!("unable to delete matching rows from {}", &self.table))?;
+
+        self.drop_temp_table(transaction).await?;
         Ok(())
     }
 
@@ -130,9 +287,61 @@ impl ImportCmd {
             &self.temp_table, &self.table
         );
         sqlx::query(query.as_str())
-            .execute(transaction)
+            .execute(&mut **transaction)
             .await
             .with_context(|| format!("unable to create table: {}", &self.temp_table))?;
+
+        // identity columns missing from the csv are only filled when inserting into the table
+        let identities = sqlx::query_scalar!(
+            r#"
+SELECT attname::text AS "name!"
+FROM pg_attribute
+WHERE attrelid = $1::text::regclass
+AND attnum > 0
+AND NOT attisdropped
+AND attidentity <> ''"#,
+            self.table
+        )
+        .fetch_all(&mut **transaction)
+        .await
+        .with_context(|| format!("unable to fetch identity columns of {}", &self.table))?;
+        for column in identities {
+            if self.header.split(',').any(|c| c.trim() == column) {
+                continue;
+            }
+            let query = format!(
+                "ALTER TABLE {} ALTER COLUMN {column} DROP NOT NULL",
+                &self.temp_table
+            );
+            sqlx::query(query.as_str())
+                .execute(&mut **transaction)
+                .await
+                .with_context(|| {
+                    format!("unable to alter column {column} of {}", &self.temp_table)
+                })?;
+        }
+
+        for column in &self.ignored_columns {
+            let query = format!("ALTER TABLE {} ADD COLUMN {column} text", &self.temp_table);
+            sqlx::query(query.as_str())
+                .execute(&mut **transaction)
+                .await
+                .with_context(|| {
+                    format!("unable to add column {column} to {}", &self.temp_table)
+                })?;
+        }
+        Ok(())
+    }
+
+    async fn drop_temp_table(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Postgres>,
+    ) -> Result<()> {
+        let query = format!("DROP TABLE {}", &self.temp_table);
+        sqlx::query(query.as_str())
+            .execute(&mut **transaction)
+            .await
+            .with_context(|| format!("unable to drop table: {}", &self.temp_table))?;
         Ok(())
     }
 
@@ -173,13 +382,13 @@ JOIN pg_class AS s
 ON s.oid = d.objid
 WHERE d.classid = 'pg_catalog.pg_class'::regclass
 AND d.refclassid = 'pg_catalog.pg_class'::regclass
-AND t.relkind IN ('r', 'P')
+AND t.relkind IN ('r', 'p')
 AND s.relkind = 'S'
 AND t.relname = $1",
             self.table
         )
         .map(|row| (row.sequence_name, row.column_name))
-        .fetch_all(transaction)
+        .fetch_all(&mut **transaction)
         .await?;
 
         Ok(rows)
@@ -190,22 +399,68 @@ AND t.relname = $1",
         transaction: &mut sqlx::Transaction<'_, Postgres>,
         sequence: &Sequence,
     ) -> Result<()> {
-        sqlx::query("SELECT setval(?, ?) FROM ?")
+        let query = format!(
+            "SELECT setval($1::regclass, (SELECT COALESCE(MAX({}), 0) + 1 FROM {}), false)",
+            &sequence.1, &self.table
+        );
+        sqlx::query(query.as_str())
             .bind(&sequence.0)
-            .bind(&sequence.1)
-            .bind(&self.table)
-            .execute(transaction)
+            .execute(&mut **transaction)
             .await?;
         Ok(())
     }
 
+    async fn get_generated_columns(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Postgres>,
+    ) -> Result<Vec<(String, bool)>> {
+        let columns = sqlx::query!(
+            r#"
+SELECT attname::text AS "name!",
+attidentity = 'a' AS "identity!"
+FROM pg_attribute
+WHERE attrelid = $1::text::regclass
+AND attnum > 0
+AND NOT attisdropped
+AND (attgenerated = 's' OR attidentity = 'a')"#,
+            self.table
+        )
+        .map(|row| (row.name, row.identity))
+        .fetch_all(&mut **transaction)
+        .await
+        .with_context(|| format!("unable to fetch generated columns of {}", &self.table))?;
+
+        Ok(columns)
+    }
+
     async fn insert_to(&self, transaction: &mut sqlx::Transaction<'_, Postgres>) -> Result<()> {
+        let generated = self.get_generated_columns(transaction).await?;
+        let columns = self
+            .header
+            .split(',')
+            .map(str::trim)
+            .filter(|column| !self.ignored_columns.iter().any(|c| c == column))
+            .filter(|column| {
+                !generated
+                    .iter()
+                    .any(|(c, identity)| c == column && !identity)
+            })
+            .collect::<Vec<_>>();
+        let overriding = match columns.iter().any(|column| {
+            generated
+                .iter()
+                .any(|(c, identity)| c == column && *identity)
+        }) {
+            true => " OVERRIDING SYSTEM VALUE",
+            false => "",
+        };
+        let columns = columns.join(",");
         let query = format!(
-            "INSERT INTO {}({}) SELECT {} FROM {} ON CONFLICT DO NOTHING",
-            &self.table, &self.header, &self.header, &self.temp_table
+            "INSERT INTO {}({}){} SELECT {} FROM {} ON CONFLICT DO NOTHING",
+            &self.table, &columns, overriding, &columns, &self.temp_table
         );
         sqlx::query(query.as_str())
-            .execute(transaction)
+            .execute(&mut **transaction)
             .await
             .with_context(|| {
                 format!(
@@ -218,18 +473,179 @@ AND t.relname = $1",
     }
 }
 
+pub struct KeyRemapper<'a> {
+    relationships: &'a [Relationship],
+    remapped: HashMap<String, String>,
+}
+
+impl<'a> KeyRemapper<'a> {
+    pub fn new(relationships: &'a [Relationship]) -> Self {
+        Self {
+            relationships,
+            remapped: HashMap::new(),
+        }
+    }
+
+    async fn remap(
+        &mut self,
+        transaction: &mut sqlx::Transaction<'_, Postgres>,
+        cmd: &ImportCmd,
+    ) -> Result<()> {
+        let keymap = format!("{}_keymap", &cmd.table);
+        let keys = get_primary_key(&mut **transaction, &cmd.table).await?;
+        let sequence = match keys.as_slice() {
+            [key] => cmd
+                .get_sequences(transaction)
+                .await?
+                .into_iter()
+                .find(|(_, column)| column == key),
+            _ => None,
+        };
+
+        if let Some((sequence, key)) = &sequence {
+            let query = format!(
+                "CREATE TEMP TABLE IF NOT EXISTS {keymap} ON COMMIT DROP AS SELECT {key} AS old_key, {key} AS new_key FROM {} WITH NO DATA",
+                &cmd.temp_table
+            );
+            sqlx::query(query.as_str())
+                .execute(&mut **transaction)
+                .await?;
+
+            let query = format!(
+                "INSERT INTO {keymap} SELECT {key}, nextval($1::regclass) FROM {} WHERE {key} NOT IN (SELECT old_key FROM {keymap})",
+                &cmd.temp_table
+            );
+            sqlx::query(query.as_str())
+                .bind(sequence)
+                .execute(&mut **transaction)
+                .await?;
+            self.remapped.insert(cmd.table.clone(), key.clone());
+        }
+
+        for rel in self.relationships.iter().filter(|rel| {
+            rel.source_table == cmd.table
+                && self
+                    .remapped
+                    .get(&rel.dest_table)
+                    .is_some_and(|key| rel.dest_columns.contains(key))
+        }) {
+            if rel.source_columns.len() != 1 {
+                bail!(
+                    "composite foreign key {}({}) to remapped {} can't be remapped",
+                    &cmd.table,
+                    rel.source_columns.join(", "),
+                    &rel.dest_table
+                );
+            }
+            let discriminator = rel
+                .discriminator
+                .as_ref()
+                .map(|d| {
+                    format!(
+                        " AND {}.{} = {}",
+                        &cmd.temp_table,
+                        d.column,
+                        quote_literal(&d.value)
+                    )
+                })
+                .unwrap_or_default();
+            // a key missing from the keymap would point at an unrelated destination row
+            let query = format!(
+                "SELECT count(*) FROM {temp} WHERE {column} IS NOT NULL{discriminator} AND NOT EXISTS (SELECT 1 FROM {dest}_keymap m WHERE m.old_key = {temp}.{column})",
+                temp = &cmd.temp_table,
+                column = &rel.source_columns[0],
+                dest = &rel.dest_table,
+            );
+            let (unmapped,) = sqlx::query_as::<_, (i64,)>(query.as_str())
+                .fetch_one(&mut **transaction)
+                .await?;
+            if unmapped > 0 {
+                bail!(
+                    "{unmapped} rows of {} reference {} rows missing from the import through {}",
+                    &cmd.table,
+                    &rel.dest_table,
+                    &rel.source_columns[0]
+                );
+            }
+            let query = format!(
+                "UPDATE {temp} SET {column} = m.new_key FROM {dest}_keymap m WHERE {temp}.{column} = m.old_key{discriminator}",
+                temp = &cmd.temp_table,
+                column = &rel.source_columns[0],
+                dest = &rel.dest_table,
+            );
+            sqlx::query(query.as_str())
+                .execute(&mut **transaction)
+                .await?;
+        }
+
+        if let Some((_, key)) = &sequence {
+            let query = format!(
+                "UPDATE {temp} SET {key} = m.new_key FROM {keymap} m WHERE {temp}.{key} = m.old_key",
+                temp = &cmd.temp_table,
+            );
+            sqlx::query(query.as_str())
+                .execute(&mut **transaction)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn truncate_tables(
+    transaction: &mut sqlx::Transaction<'_, Postgres>,
+    tables: &[&str],
+) -> Result<()> {
+    if tables.is_empty() {
+        return Ok(());
+    }
+
+    let query = format!("TRUNCATE {}", tables.join(", "));
+    sqlx::query(query.as_str())
+        .execute(&mut **transaction)
+        .await
+        .with_context(|| format!("unable to truncate tables: {}", tables.join(", ")))?;
+    Ok(())
+}
+
+pub async fn get_primary_key<'c>(
+    executor: impl Executor<'c, Database = Postgres>,
+    table: &str,
+) -> Result<Vec<String>> {
+    let keys = sqlx::query_scalar!(
+        r#"
+SELECT a.attname::text AS "name!"
+FROM pg_index i
+JOIN pg_class t ON t.oid = i.indrelid
+JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = ANY(i.indkey)
+WHERE i.indisprimary
+AND t.relname = $1"#,
+        table
+    )
+    .fetch_all(executor)
+    .await
+    .with_context(|| format!("unable to fetch primary key of {table}"))?;
+
+    Ok(keys)
+}
+
 pub async fn get_relationships(pool: &Pool<Postgres>) -> Result<Vec<Relationship>> {
     let relationships = sqlx::query!(
-        "
+        r#"
 SELECT
-x.table_name as source_table,
-x.column_name as source_column,
-y.table_name as dest_table,
-y.column_name as dest_column
-FROM information_schema.referential_constraints c
-JOIN information_schema.key_column_usage x on x.constraint_name = c.constraint_name
-JOIN information_schema.key_column_usage y on y.ordinal_position = x.position_in_unique_constraint
-AND y.constraint_name = c.unique_constraint_name"
+c.conname::text AS "constraint_name!",
+src.relname::text AS "source_table!",
+array_agg(sa.attname::text ORDER BY k.position) AS "source_columns!",
+dst.relname::text AS "dest_table!",
+array_agg(da.attname::text ORDER BY k.position) AS "dest_columns!"
+FROM pg_constraint c
+CROSS JOIN LATERAL unnest(c.conkey, c.confkey) WITH ORDINALITY AS k(source_attnum, dest_attnum, position)
+JOIN pg_attribute sa ON sa.attrelid = c.conrelid AND sa.attnum = k.source_attnum
+JOIN pg_attribute da ON da.attrelid = c.confrelid AND da.attnum = k.dest_attnum
+JOIN pg_class src ON src.oid = COALESCE(pg_partition_root(c.conrelid), c.conrelid)
+JOIN pg_class dst ON dst.oid = COALESCE(pg_partition_root(c.confrelid), c.confrelid)
+WHERE c.contype = 'f'
+AND c.conparentid = 0
+GROUP BY c.oid, c.conname, src.relname, dst.relname"#
     )
     .fetch_all(pool)
     .await
@@ -237,33 +653,160 @@ AND y.constraint_name = c.unique_constraint_name"
 
     Ok(relationships
         .into_iter()
-        .filter_map(|rel| {
-            let source_table = rel.source_table?;
-            let dest_table = rel.dest_table?;
-            let source_column = rel.source_column?;
-            let dest_column = rel.dest_column?;
-            Some(Relationship {
-                source_table,
-                dest_table,
-                source_column,
-                dest_column,
-            })
+        .map(|rel| Relationship {
+            constraint_name: Some(rel.constraint_name),
+            source_table: rel.source_table,
+            dest_table: rel.dest_table,
+            source_columns: rel.source_columns,
+            dest_columns: rel.dest_columns,
+            discriminator: None,
+        })
+        .collect())
+}
+
+pub async fn get_generic_relationships(
+    pool: &Pool<Postgres>,
+    rel: &GenericRelationship,
+    tables: &[String],
+) -> Result<Vec<Relationship>> {
+    let mut targets = Vec::new();
+    for (value, table) in rel.targets.iter().flatten() {
+        if !tables.contains(table) {
+            bail!(
+                "generic relationship from {} targets unknown table {table}",
+                rel.source_table
+            );
+        }
+        targets.push((value.clone(), table.clone()));
+    }
+
+    if let Some(lookup_query) = &rel.lookup_query {
+        let lookup = sqlx::query_as::<_, (String, String)>(lookup_query)
+            .fetch_all(pool)
+            .await
+            .with_context(|| format!("unable to run lookup query of {}", rel.source_table))?;
+        // stale lookup rows (e.g. content types of dropped models) are skipped
+        targets.extend(
+            lookup
+                .into_iter()
+                .filter(|(_, table)| tables.contains(table)),
+        );
+    }
+
+    Ok(targets
+        .into_iter()
+        .map(|(value, dest_table)| Relationship {
+            constraint_name: None,
+            source_table: rel.source_table.clone(),
+            dest_table,
+            source_columns: vec![rel.id_column.clone()],
+            dest_columns: vec![rel.dest_column.clone().unwrap_or_else(|| "id".to_string())],
+            discriminator: Some(Discriminator {
+                column: rel.type_column.clone(),
+                value,
+            }),
         })
         .collect())
 }
 
 pub async fn get_all_tables(pool: &Pool<Postgres>) -> Result<Vec<String>> {
-    let tables = sqlx::query!(
-        "
-SELECT cls.relname as name
+    let tables = sqlx::query_scalar!(
+        r#"
+SELECT cls.relname::text AS "name!"
 FROM pg_class cls
 JOIN pg_namespace nsp ON nsp.oid = cls.relnamespace
 WHERE nsp.nspname NOT IN ('information_schema', 'pg_catalog')
-AND cls.relkind = 'r'"
+AND cls.relkind IN ('r', 'p')
+AND NOT cls.relispartition"#
     )
     .fetch_all(pool)
     .await
     .with_context(|| "unable to fetch_all list of all tables")?;
 
-    Ok(tables.into_iter().filter_map(|t| t.name).collect())
+    Ok(tables)
+}
+
+/// the planner's estimate of the rows of a table, once it has been analyzed. partitioned tables have
+/// no statistics of their own, so theirs is the sum of their leaf partitions'
+pub async fn estimate_rows(pool: &Pool<Postgres>, table: &str) -> Result<Option<u64>> {
+    let reltuples = sqlx::query_scalar!(
+        r#"
+SELECT CASE WHEN bool_and(reltuples >= 0) THEN sum(reltuples) END AS reltuples
+FROM pg_class
+WHERE (oid = $1::text::regclass AND relkind <> 'p')
+OR oid IN (SELECT relid FROM pg_partition_tree($1::text::regclass) WHERE isleaf)"#,
+        table
+    )
+    .fetch_one(pool)
+    .await
+    .with_context(|| format!("unable to estimate the rows of {table}"))?;
+
+    Ok(reltuples.map(|reltuples| reltuples as u64))
+}
+
+pub async fn get_partitions(pool: &Pool<Postgres>, table: &str) -> Result<Vec<String>> {
+    let partitions = sqlx::query_scalar!(
+        r#"
+SELECT relid::regclass::text AS "name!"
+FROM pg_partition_tree($1::text::regclass)
+WHERE isleaf"#,
+        table
+    )
+    .fetch_all(pool)
+    .await
+    .with_context(|| format!("unable to fetch partitions of {table}"))?;
+
+    Ok(partitions)
+}
+
+pub async fn sample_column(
+    pool: &Pool<Postgres>,
+    table: &str,
+    column: &str,
+    limit: i64,
+) -> Result<Vec<String>> {
+    let values = sqlx::query_as::<_, (String,)>(&format!(
+        "SELECT {column}::text FROM {table} WHERE {column} IS NOT NULL LIMIT $1"
+    ))
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .with_context(|| format!("unable to sample {table}.{column}"))?;
+
+    Ok(values.into_iter().map(|(value,)| value).collect())
+}
+
+pub async fn get_columns(pool: &Pool<Postgres>) -> Result<HashMap<String, Vec<Column>>> {
+    let rows = sqlx::query!(
+        r#"
+SELECT cls.relname::text AS "table!",
+att.attname::text AS "name!",
+format_type(att.atttypid, att.atttypmod) AS "data_type!",
+att.attnotnull,
+att.atthasdef OR att.attidentity <> '' AS "has_default!",
+att.attgenerated = 's' AS "generated!"
+FROM pg_attribute att
+JOIN pg_class cls ON cls.oid = att.attrelid
+JOIN pg_namespace nsp ON nsp.oid = cls.relnamespace
+WHERE nsp.nspname NOT IN ('information_schema', 'pg_catalog')
+AND cls.relkind IN ('r', 'p')
+AND att.attnum > 0
+AND NOT att.attisdropped
+ORDER BY cls.relname, att.attnum"#
+    )
+    .fetch_all(pool)
+    .await
+    .with_context(|| "unable to fetch list of columns")?;
+
+    let mut columns: HashMap<String, Vec<Column>> = HashMap::new();
+    for row in rows {
+        columns.entry(row.table).or_default().push(Column {
+            name: row.name,
+            data_type: row.data_type,
+            not_null: row.attnotnull,
+            has_default: row.has_default,
+            generated: row.generated,
+        });
+    }
+    Ok(columns)
 }
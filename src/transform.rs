@@ -1,73 +1,801 @@
-use crate::config::TransformKind;
+use crate::config::{ParameterisedTransform, TransformConfig, TransformKind};
+use crate::sql::Column;
+use anyhow::{bail, Context, Result};
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
+use chrono::{Duration, NaiveDate, NaiveDateTime};
 use fake::{
-    faker::{
-        internet::en::{FreeEmail, Username},
-        name::en::{FirstName, LastName},
-    },
+    faker::{address, company, internet, lorem::en as lorem, name, phone_number},
+    rand::{rngs::StdRng, Rng, SeedableRng},
     Fake,
 };
 
 pub type Transforms = HashMap<String, TableTransform>;
 pub type TableTransform = HashMap<String, Transform>;
 
+/// a `{column}` placeholder of a template
+const PLACEHOLDER: &str = r"\{(\w+)\}";
+
+type Func = Box<dyn Fn(Option<&str>, &Row) -> Option<String> + Send + Sync>;
+
+/// `func` gets `None` for NULL fields and returns `None` to write a NULL
 pub struct Transform {
-    pub func: fn(&str) -> String,
+    pub func: Func,
+    uses_row: bool,
 }
 impl Transform {
-    pub fn new(name: &TransformKind) -> Self {
-        match name {
-            TransformKind::ClearField => Self { func: clear_field },
-            TransformKind::FirstNameEn => Self {
-                func: first_name_en,
-            },
-            TransformKind::LastNameEn => Self { func: last_name_en },
-            TransformKind::UsernameEn => Self { func: username_en },
-            TransformKind::EmailEn => Self { func: email_en },
-            TransformKind::DjangoGarbagePassword => Self {
-                func: django_garbage_password,
-            },
+    /// random transforms draw from `rng`, nested transforms from generators derived from it
+    pub fn new(
+        config: &TransformConfig,
+        registry: &TransformRegistry,
+        mut rng: StdRng,
+    ) -> Result<Self> {
+        let func = match config {
+            TransformConfig::Parameterised(ParameterisedTransform::Sql { .. }) => {
+                bail!("sql transforms are applied by postgres and can't be combined with others")
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::Custom { name }) => {
+                let custom = registry
+                    .custom
+                    .get(name)
+                    .cloned()
+                    .with_context(|| format!("custom transform {name} is not registered"))?;
+                return Ok(Self {
+                    uses_row: custom.uses_row(),
+                    func: Box::new(move |input, row| custom.transform(input, row)),
+                });
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::JsonKeys {
+                keys,
+                transform,
+            }) => {
+                let keys = keys.clone();
+                let transform = Transform::new(transform, registry, StdRng::from_rng(&mut rng))?;
+                return Ok(Self {
+                    uses_row: transform.uses_row,
+                    func: Box::new(move |input, row| {
+                        input.map(|input| {
+                            let mut json = match serde_json::from_str(input) {
+                                Ok(json) => json,
+                                Err(_) => return input.to_owned(),
+                            };
+                            mask_json_keys(&mut json, &keys, &|value| (transform.func)(value, row));
+                            json.to_string()
+                        })
+                    }),
+                });
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::ArrayElements { transform }) => {
+                let transform = Transform::new(transform, registry, StdRng::from_rng(&mut rng))?;
+                return Ok(Self {
+                    uses_row: transform.uses_row,
+                    func: Box::new(move |input, row| {
+                        input.map(|input| match parse_array(input) {
+                            Some(elements) => write_array(
+                                elements
+                                    .iter()
+                                    .map(|element| (transform.func)(element.as_deref(), row)),
+                            ),
+                            None => input.to_owned(),
+                        })
+                    }),
+                });
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::Template { template }) => {
+                let placeholder = Regex::new(PLACEHOLDER)?;
+                let template = template.clone();
+                return Ok(Self::row_aware(Box::new(move |input, row| {
+                    input.map(|_| {
+                        placeholder
+                            .replace_all(&template, |caps: &Captures| {
+                                row.get(&caps[1]).unwrap_or_default().to_owned()
+                            })
+                            .into_owned()
+                    })
+                })));
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::Conditional {
+                column,
+                equals,
+                transform,
+                otherwise,
+            }) => {
+                let column = column.clone();
+                let equals = equals.clone();
+                let transform = Transform::new(transform, registry, StdRng::from_rng(&mut rng))?;
+                let otherwise = otherwise
+                    .as_deref()
+                    .map(|otherwise| {
+                        Transform::new(otherwise, registry, StdRng::from_rng(&mut rng))
+                    })
+                    .transpose()?;
+                return Ok(Self::row_aware(Box::new(move |input, row| {
+                    if row.get(&column) == Some(equals.as_str()) {
+                        (transform.func)(input, row)
+                    } else if let Some(otherwise) = &otherwise {
+                        (otherwise.func)(input, row)
+                    } else {
+                        input.map(str::to_owned)
+                    }
+                })));
+            }
+            TransformConfig::Named(kind) => named(kind, rng),
+            TransformConfig::Parameterised(ParameterisedTransform::Constant { value }) => {
+                let value = value.clone();
+                non_null(move |_| value.clone())
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::RegexReplace {
+                pattern,
+                replacement,
+            }) => {
+                let regex = Regex::new(pattern)
+                    .with_context(|| format!("invalid regex_replace pattern {pattern}"))?;
+                let replacement = replacement.clone();
+                non_null(move |input| regex.replace_all(input, replacement.as_str()).into_owned())
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::Truncate { len }) => {
+                let len = *len;
+                non_null(move |input| input.chars().take(len).collect())
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::NumericNoise { pct }) => {
+                let pct = *pct;
+                random(move |input, rng| numeric_noise(input, pct, rng), rng)
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::Round { digits }) => {
+                let digits = *digits;
+                non_null(move |input| round(input, digits))
+            }
+            TransformConfig::Parameterised(ParameterisedTransform::DateShift { max_days }) => {
+                let max_days = *max_days;
+                random(move |input, rng| date_shift(input, max_days, rng), rng)
+            }
+        };
+
+        Ok(Self {
+            func,
+            uses_row: false,
+        })
+    }
+
+    /// a transform of a json column applying each transform at its path
+    pub fn json_paths(paths: Vec<(Vec<String>, Transform)>) -> Self {
+        let uses_row = paths.iter().any(|(_, transform)| transform.uses_row);
+        Self {
+            uses_row,
+            func: Box::new(move |input, row| {
+                input.map(|input| {
+                    let mut json = match serde_json::from_str(input) {
+                        Ok(json) => json,
+                        Err(_) => return input.to_owned(),
+                    };
+                    for (path, transform) in &paths {
+                        mask_json_path(&mut json, path, &|value| (transform.func)(value, row));
+                    }
+                    json.to_string()
+                })
+            }),
+        }
+    }
+
+    fn row_aware(func: Func) -> Self {
+        Self {
+            func,
+            uses_row: true,
+        }
+    }
+}
+
+/// a transform provided by code embedding pgsubset, referenced in the config as
+/// `{ kind = "custom", name = "<name>" }`
+pub trait CustomTransform: Send + Sync {
+    /// `input` is `None` for NULL fields, returning `None` writes a NULL
+    fn transform(&self, input: Option<&str>, row: &Row) -> Option<String>;
+
+    /// whether the transform reads other columns and so has to run after the others
+    fn uses_row(&self) -> bool {
+        false
+    }
+}
+
+impl<F> CustomTransform for F
+where
+    F: Fn(Option<&str>, &Row) -> Option<String> + Send + Sync,
+{
+    fn transform(&self, input: Option<&str>, row: &Row) -> Option<String> {
+        self(input, row)
+    }
+
+    /// a closure can't tell whether it reads the row, so it sees the other columns masked
+    fn uses_row(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Default)]
+pub struct TransformRegistry {
+    custom: HashMap<String, Arc<dyn CustomTransform>>,
+}
+impl TransformRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, name: &str, transform: impl CustomTransform + 'static) -> &mut Self {
+        self.custom.insert(name.to_owned(), Arc::new(transform));
+        self
+    }
+}
+
+/// checks that the values written by a transform are valid for the column
+pub fn check_column(config: &TransformConfig, column: &Column) -> Result<()> {
+    let data_type = column.data_type.as_str();
+    if matches!(config, TransformConfig::Named(TransformKind::SetNull)) && column.not_null {
+        bail!("set_null can't be applied to a NOT NULL column");
+    }
+    if !accepts(config, data_type) {
+        bail!("transform can't write values of type {data_type}");
+    }
+    Ok(())
+}
+
+fn accepts(config: &TransformConfig, data_type: &str) -> bool {
+    let kind = match config {
+        TransformConfig::Named(kind) => kind,
+        TransformConfig::Parameterised(transform) => {
+            return match transform {
+                ParameterisedTransform::NumericNoise { .. }
+                | ParameterisedTransform::Round { .. } => {
+                    is_numeric(data_type) || is_textual(data_type)
+                }
+                ParameterisedTransform::DateShift { .. } => {
+                    is_temporal(data_type) || is_textual(data_type)
+                }
+                ParameterisedTransform::JsonKeys { .. } => data_type.starts_with("json"),
+                ParameterisedTransform::ArrayElements { transform } => data_type
+                    .strip_suffix("[]")
+                    .is_some_and(|element_type| accepts(transform, element_type)),
+                ParameterisedTransform::Conditional {
+                    transform,
+                    otherwise,
+                    ..
+                } => {
+                    accepts(transform, data_type)
+                        && otherwise.as_deref().map_or(true, |o| accepts(o, data_type))
+                }
+                _ => true,
+            };
+        }
+    };
+
+    match kind {
+        TransformKind::SetNull => true,
+        TransformKind::Uuid => data_type == "uuid" || is_textual(data_type),
+        TransformKind::Ipv4 | TransformKind::Ipv6 => {
+            matches!(data_type, "inet" | "cidr") || is_textual(data_type)
         }
+        TransformKind::DateJitter => is_temporal(data_type) || is_textual(data_type),
+        _ => is_textual(data_type),
     }
 }
 
+pub fn is_textual(data_type: &str) -> bool {
+    !data_type.ends_with("[]")
+        && ["text", "character", "citext"]
+            .iter()
+            .any(|t| data_type.starts_with(t))
+}
+
+fn is_numeric(data_type: &str) -> bool {
+    [
+        "smallint",
+        "integer",
+        "bigint",
+        "numeric",
+        "real",
+        "double precision",
+    ]
+    .iter()
+    .any(|t| data_type.starts_with(t))
+        && !data_type.ends_with("[]")
+}
+
+fn is_temporal(data_type: &str) -> bool {
+    (data_type == "date" || data_type.starts_with("timestamp")) && !data_type.ends_with("[]")
+}
+
+/// the fields of the row being transformed, by column name
+pub struct Row<'a> {
+    header: &'a [String],
+    fields: &'a [Option<String>],
+}
+impl Row<'_> {
+    /// `None` for NULL fields and unknown columns
+    pub fn get(&self, column: &str) -> Option<&str> {
+        let index = self.header.iter().position(|h| h == column)?;
+        self.fields.get(index)?.as_deref()
+    }
+}
+
+/// NULL fields are kept as they are
+fn non_null(func: impl Fn(&str) -> String + Send + Sync + 'static) -> Func {
+    Box::new(move |input, _| input.map(&func))
+}
+
+/// like `non_null`, for transforms drawing from `rng`
+fn random(func: impl Fn(&str, &mut StdRng) -> String + Send + Sync + 'static, rng: StdRng) -> Func {
+    // each table is written by a single task, so the lock is never contended
+    let rng = Mutex::new(rng);
+    Box::new(move |input, _| {
+        let mut rng = rng.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        input.map(|input| func(input, &mut rng))
+    })
+}
+
+/// the generator of the transforms of a column, derived from the seed so an export can be reproduced
+pub fn column_rng(seed: Option<&str>, table: &str, column: &str) -> StdRng {
+    match seed {
+        Some(seed) => {
+            let digest = Sha256::digest(format!("{seed}\0{table}\0{column}"));
+            StdRng::from_seed(digest.into())
+        }
+        None => StdRng::from_os_rng(),
+    }
+}
+
+fn named(kind: &TransformKind, rng: StdRng) -> Func {
+    let func: fn(&str, &mut StdRng) -> String = match kind {
+        TransformKind::SetNull => return Box::new(|_, _| None),
+        TransformKind::ClearField => return non_null(clear_field),
+        TransformKind::DjangoGarbagePassword => return non_null(django_garbage_password),
+        TransformKind::FirstNameEn => first_name_en,
+        TransformKind::FirstNameFr => first_name_fr,
+        TransformKind::FirstNameIt => first_name_it,
+        TransformKind::FirstNameDe => first_name_de,
+        TransformKind::LastNameEn => last_name_en,
+        TransformKind::LastNameFr => last_name_fr,
+        TransformKind::LastNameIt => last_name_it,
+        TransformKind::LastNameDe => last_name_de,
+        TransformKind::NameEn => name_en,
+        TransformKind::NameFr => name_fr,
+        TransformKind::NameIt => name_it,
+        TransformKind::NameDe => name_de,
+        TransformKind::UsernameEn => username_en,
+        TransformKind::EmailEn => email_en,
+        TransformKind::EmailFr => email_fr,
+        TransformKind::EmailIt => email_it,
+        TransformKind::EmailDe => email_de,
+        TransformKind::PhoneNumberEn => phone_number_en,
+        TransformKind::PhoneNumberFr => phone_number_fr,
+        TransformKind::PhoneNumberIt => phone_number_it,
+        TransformKind::PhoneNumberDe => phone_number_de,
+        TransformKind::StreetAddressEn => street_address_en,
+        TransformKind::StreetAddressFr => street_address_fr,
+        TransformKind::StreetAddressIt => street_address_it,
+        TransformKind::StreetAddressDe => street_address_de,
+        TransformKind::CityEn => city_en,
+        TransformKind::CityFr => city_fr,
+        TransformKind::CityIt => city_it,
+        TransformKind::CityDe => city_de,
+        TransformKind::ZipCodeEn => zip_code_en,
+        TransformKind::ZipCodeFr => zip_code_fr,
+        TransformKind::ZipCodeIt => zip_code_it,
+        TransformKind::ZipCodeDe => zip_code_de,
+        TransformKind::CompanyNameEn => company_name_en,
+        TransformKind::CompanyNameFr => company_name_fr,
+        TransformKind::CompanyNameIt => company_name_it,
+        TransformKind::CompanyNameDe => company_name_de,
+        TransformKind::Iban => iban,
+        TransformKind::Ipv4 => ipv4,
+        TransformKind::Ipv6 => ipv6,
+        TransformKind::Uuid => uuid,
+        TransformKind::DateJitter => date_jitter,
+        TransformKind::CreditCardNumber => credit_card_number,
+        TransformKind::LoremWord => lorem_word,
+        TransformKind::LoremSentence => lorem_sentence,
+        TransformKind::LoremParagraph => lorem_paragraph,
+    };
+    random(func, rng)
+}
+
+/// row aware transforms are applied last, so they see the results of the other ones
 #[derive(Default)]
 pub struct IndexedTransforms<'a> {
-    pub transforms: HashMap<usize, &'a Transform>,
+    header: Vec<String>,
+    transforms: Vec<(usize, &'a Transform)>,
+    affected: Vec<u64>,
 }
 impl<'a> IndexedTransforms<'a> {
-    pub fn new(transforms: &'a HashMap<String, Transform>, header: &[String]) -> Self {
-        let ts_with_idx: HashMap<_, _> = header
+    pub fn new(transforms: &'a HashMap<String, Transform>, header: Vec<String>) -> Self {
+        let mut ts_with_idx: Vec<_> = header
             .iter()
             .enumerate()
             .filter_map(|(idx, h)| transforms.get(h).map(|t| (idx, t)))
             .collect();
+        ts_with_idx.sort_by_key(|(idx, t)| (t.uses_row, *idx));
 
         Self {
+            header,
+            affected: vec![0; ts_with_idx.len()],
             transforms: ts_with_idx,
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    pub fn apply(&mut self, fields: &mut [Option<String>]) {
+        for ((index, transform), affected) in self.transforms.iter().zip(&mut self.affected) {
+            if *index < fields.len() {
+                let row = Row {
+                    header: &self.header,
+                    fields,
+                };
+                let value = (transform.func)(fields[*index].as_deref(), &row);
+                if value != fields[*index] {
+                    *affected += 1;
+                    fields[*index] = value;
+                }
+            }
+        }
+    }
+
+    /// number of rows changed by the transform of each column
+    pub fn affected_rows(&self) -> HashMap<String, u64> {
+        self.transforms
+            .iter()
+            .zip(&self.affected)
+            .map(|((index, _), affected)| (self.header[*index].clone(), *affected))
+            .collect()
+    }
+}
+
+macro_rules! fakers {
+    ($($func:ident => $faker:expr;)+) => {
+        $(
+            pub fn $func(_input: &str, rng: &mut StdRng) -> String {
+                $faker.fake_with_rng(rng)
+            }
+        )+
+    };
+}
+
+fakers! {
+    first_name_en => name::en::FirstName();
+    first_name_fr => name::fr_fr::FirstName();
+    first_name_it => name::it_it::FirstName();
+    first_name_de => name::de_de::FirstName();
+    last_name_en => name::en::LastName();
+    last_name_fr => name::fr_fr::LastName();
+    last_name_it => name::it_it::LastName();
+    last_name_de => name::de_de::LastName();
+    name_en => name::en::Name();
+    name_fr => name::fr_fr::Name();
+    name_it => name::it_it::Name();
+    name_de => name::de_de::Name();
+    username_en => internet::en::Username();
+    email_en => internet::en::FreeEmail();
+    email_fr => internet::fr_fr::FreeEmail();
+    email_it => internet::it_it::FreeEmail();
+    email_de => internet::de_de::FreeEmail();
+    phone_number_en => phone_number::en::PhoneNumber();
+    phone_number_fr => phone_number::fr_fr::PhoneNumber();
+    phone_number_it => phone_number::it_it::PhoneNumber();
+    phone_number_de => phone_number::de_de::PhoneNumber();
+    city_en => address::en::CityName();
+    city_fr => address::fr_fr::CityName();
+    city_it => address::it_it::CityName();
+    city_de => address::de_de::CityName();
+    zip_code_en => address::en::ZipCode();
+    zip_code_fr => address::fr_fr::ZipCode();
+    zip_code_it => address::it_it::ZipCode();
+    zip_code_de => address::de_de::ZipCode();
+    company_name_en => company::en::CompanyName();
+    company_name_fr => company::fr_fr::CompanyName();
+    company_name_it => company::it_it::CompanyName();
+    company_name_de => company::de_de::CompanyName();
+    ipv4 => internet::en::IPv4();
+    ipv6 => internet::en::IPv6();
+    lorem_word => lorem::Word();
+    lorem_sentence => lorem::Sentence(4..12);
+    lorem_paragraph => lorem::Paragraph(3..6);
+}
+
+macro_rules! street_addresses {
+    ($($func:ident => $locale:ident;)+) => {
+        $(
+            pub fn $func(_input: &str, rng: &mut StdRng) -> String {
+                let street: String = address::$locale::StreetName().fake_with_rng(rng);
+                let number: String = address::$locale::BuildingNumber().fake_with_rng(rng);
+                format!("{street} {number}")
+            }
+        )+
+    };
+}
+
+street_addresses! {
+    street_address_en => en;
+    street_address_fr => fr_fr;
+    street_address_it => it_it;
+    street_address_de => de_de;
 }
 
 pub fn clear_field(_input: &str) -> String {
     "".into()
 }
 
-pub fn first_name_en(_input: &str) -> String {
-    FirstName().fake()
+pub fn iban(_input: &str, rng: &mut StdRng) -> String {
+    let bban: String = (0..18)
+        .map(|_| char::from(b'0' + rng.random_range(0..10)))
+        .collect();
+    // mod 97 of the bban followed by "DE00", with letters mapped to 10..35
+    let remainder = format!("{bban}131400")
+        .bytes()
+        .fold(0, |acc, digit| (acc * 10 + u32::from(digit - b'0')) % 97);
+    format!("DE{:02}{bban}", 98 - remainder)
+}
+
+/// a visa, mastercard, amex or discover number, with the check digit computed from the right
+/// (fake's own generator gets it wrong for odd lengths)
+pub fn credit_card_number(_input: &str, rng: &mut StdRng) -> String {
+    const ISSUERS: [(&str, usize); 7] = [
+        ("4", 16),
+        ("4", 13),
+        ("51", 16),
+        ("55", 16),
+        ("34", 15),
+        ("37", 15),
+        ("6011", 16),
+    ];
+    let (prefix, len) = ISSUERS[rng.random_range(0..ISSUERS.len())];
+    let mut digits: Vec<u32> = prefix.bytes().map(|b| u32::from(b - b'0')).collect();
+    while digits.len() < len - 1 {
+        digits.push(rng.random_range(0..10));
+    }
+    // every second digit is doubled, starting with the one next to the check digit
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| match (i % 2 == 0, digit * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => *digit,
+        })
+        .sum();
+    digits.push((10 - sum % 10) % 10);
+    digits.iter().map(|digit| digit.to_string()).collect()
+}
+
+pub fn uuid(_input: &str, rng: &mut StdRng) -> String {
+    let mut bytes: [u8; 16] = rng.random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+pub fn date_jitter(input: &str, rng: &mut StdRng) -> String {
+    date_shift(input, 30, rng)
+}
+
+/// shifts dates and timestamps by up to `max_days`, keeping any fractional seconds or time zone suffix
+pub fn date_shift(input: &str, max_days: u32, rng: &mut StdRng) -> String {
+    let max_days = i64::from(max_days);
+    let jitter = Duration::days(rng.random_range(-max_days..=max_days));
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return (date + jitter).format("%Y-%m-%d").to_string();
+    }
+
+    let datetime = input
+        .get(..19)
+        .and_then(|datetime| NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S").ok());
+    match datetime {
+        Some(datetime) => format!(
+            "{}{}",
+            (datetime + jitter).format("%Y-%m-%d %H:%M:%S"),
+            &input[19..]
+        ),
+        None => input.into(),
+    }
+}
+
+/// adds up to `pct` percent of noise, keeping the number of decimal places of the input
+pub fn numeric_noise(input: &str, pct: f64, rng: &mut StdRng) -> String {
+    let value = match input.parse::<f64>() {
+        Ok(value) if pct > 0.0 => value,
+        _ => return input.into(),
+    };
+    let noisy = value * (1.0 + rng.random_range(-pct..=pct) / 100.0);
+    let decimals = input.split_once('.').map(|(_, d)| d.len()).unwrap_or(0);
+    format!("{noisy:.decimals$}")
 }
 
-pub fn last_name_en(_input: &str) -> String {
-    LastName().fake()
+/// rounds to `digits` decimal places, or to tens, hundreds... when negative
+pub fn round(input: &str, digits: i32) -> String {
+    let value = match input.parse::<f64>() {
+        Ok(value) => value,
+        Err(_) => return input.into(),
+    };
+    let factor = 10f64.powi(digits);
+    let decimals = digits.max(0) as usize;
+    format!("{:.decimals$}", (value * factor).round() / factor)
 }
 
-pub fn email_en(_input: &str) -> String {
-    FreeEmail().fake()
+/// applies `mask` to the values of `keys` anywhere in the document
+fn mask_json_keys(
+    json: &mut serde_json::Value,
+    keys: &[String],
+    mask: &dyn Fn(Option<&str>) -> Option<String>,
+) {
+    match json {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if keys.contains(key) {
+                    *value = mask_json_value(value, mask);
+                } else {
+                    mask_json_keys(value, keys, mask);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                mask_json_keys(value, keys, mask);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// the other columns of the row a transform reads, through templates and conditions
+pub fn row_columns(config: &TransformConfig) -> Vec<String> {
+    match config {
+        TransformConfig::Parameterised(ParameterisedTransform::Template { template }) => {
+            Regex::new(PLACEHOLDER)
+                .expect("valid placeholder pattern")
+                .captures_iter(template)
+                .map(|caps| caps[1].to_owned())
+                .collect()
+        }
+        TransformConfig::Parameterised(ParameterisedTransform::Conditional {
+            column,
+            transform,
+            otherwise,
+            ..
+        }) => std::iter::once(column.clone())
+            .chain(row_columns(transform))
+            .chain(
+                otherwise
+                    .iter()
+                    .flat_map(|otherwise| row_columns(otherwise)),
+            )
+            .collect(),
+        TransformConfig::Parameterised(
+            ParameterisedTransform::JsonKeys { transform, .. }
+            | ParameterisedTransform::ArrayElements { transform },
+        ) => row_columns(transform),
+        _ => Vec::new(),
+    }
+}
+
+/// splits `column.$.a.b` into the column and the path segments
+pub fn split_json_path(key: &str) -> Option<(&str, Vec<String>)> {
+    let (column, path) = key.split_once(".$")?;
+    let segments = path
+        .split('.')
+        .skip(1)
+        .map(|segment| segment.to_owned())
+        .collect();
+    Some((column, segments))
+}
+
+/// applies `mask` to the values at `path`, where `*` matches every key or element
+fn mask_json_path(
+    json: &mut serde_json::Value,
+    path: &[String],
+    mask: &dyn Fn(Option<&str>) -> Option<String>,
+) {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            *json = mask_json_value(json, mask);
+            return;
+        }
+    };
+    match json {
+        serde_json::Value::Object(map) if segment == "*" => {
+            for value in map.values_mut() {
+                mask_json_path(value, rest, mask);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            if let Some(value) = map.get_mut(segment) {
+                mask_json_path(value, rest, mask);
+            }
+        }
+        serde_json::Value::Array(values) if segment == "*" => {
+            for value in values {
+                mask_json_path(value, rest, mask);
+            }
+        }
+        serde_json::Value::Array(values) => {
+            if let Some(value) = segment.parse().ok().and_then(|i: usize| values.get_mut(i)) {
+                mask_json_path(value, rest, mask);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn mask_json_value(
+    value: &serde_json::Value,
+    mask: &dyn Fn(Option<&str>) -> Option<String>,
+) -> serde_json::Value {
+    let masked = match value {
+        serde_json::Value::Null => mask(None),
+        serde_json::Value::String(value) => mask(Some(value)),
+        value => mask(Some(&value.to_string())),
+    };
+    masked.map_or(serde_json::Value::Null, serde_json::Value::String)
+}
+
+/// elements of a one dimensional array literal such as `{a,"b c",NULL}`
+fn parse_array(input: &str) -> Option<Vec<Option<String>>> {
+    let inner = input.strip_prefix('{')?.strip_suffix('}')?;
+    if inner.starts_with('{') {
+        return None;
+    }
+    if inner.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let mut elements = Vec::new();
+    let mut chars = inner.chars();
+    loop {
+        let mut element = String::new();
+        let mut quoted = false;
+        let mut next = chars.next();
+        if next == Some('"') {
+            quoted = true;
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => element.extend(chars.next()),
+                    '"' => break,
+                    c => element.push(c),
+                }
+            }
+            next = chars.next();
+        }
+        while let Some(c) = next.filter(|c| *c != ',') {
+            element.push(c);
+            next = chars.next();
+        }
+
+        elements.push((quoted || element != "NULL").then_some(element));
+        if next.is_none() {
+            return Some(elements);
+        }
+    }
 }
 
-pub fn username_en(_input: &str) -> String {
-    Username().fake()
+fn write_array(elements: impl Iterator<Item = Option<String>>) -> String {
+    let elements: Vec<_> = elements
+        .map(|element| match element {
+            None => "NULL".to_owned(),
+            Some(element) => format!("\"{}\"", element.replace('\\', "\\\\").replace('"', "\\\"")),
+        })
+        .collect();
+    format!("{{{}}}", elements.join(","))
 }
 
 pub fn django_garbage_password(_input: &str) -> String {
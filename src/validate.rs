@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::sql::Column;
+use crate::transform::{row_columns, split_json_path};
+
+/// the live schema the names used in a config are checked against
+pub struct Schema<'a> {
+    pub columns: &'a HashMap<String, Vec<Column>>,
+    pub constraints: Vec<&'a str>,
+}
+
+impl Schema<'_> {
+    /// lists the tables, columns and constraints of the config which don't exist
+    pub fn check(&self, cfg: &Config) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Some(target_table) = &cfg.target_table {
+            self.table(&mut problems, "target_table", target_table);
+        }
+
+        for m2m in cfg.m2m_tables.iter().flatten() {
+            self.table(&mut problems, "m2m_tables", &m2m.name);
+            self.table(&mut problems, "m2m_tables", &m2m.source);
+        }
+
+        for rel in cfg.virtual_relationships.iter().flatten() {
+            let context = "virtual_relationships";
+            self.columns(
+                &mut problems,
+                context,
+                &rel.source_table,
+                &rel.source_columns,
+            );
+            self.columns(&mut problems, context, &rel.dest_table, &rel.dest_columns);
+            if let Some(discriminator) = &rel.discriminator {
+                self.column(
+                    &mut problems,
+                    context,
+                    &rel.source_table,
+                    &discriminator.column,
+                );
+            }
+        }
+
+        for rel in cfg.generic_relationships.iter().flatten() {
+            let context = "generic_relationships";
+            self.column(&mut problems, context, &rel.source_table, &rel.type_column);
+            self.column(&mut problems, context, &rel.source_table, &rel.id_column);
+            let dest_column = rel.dest_column.as_deref().unwrap_or("id");
+            for table in rel.targets.iter().flat_map(|targets| targets.values()) {
+                self.column(&mut problems, context, table, dest_column);
+            }
+        }
+
+        for ignored in cfg.ignored_relationships.iter().flatten() {
+            let context = "ignored_relationships";
+            if let Some(constraint) = &ignored.constraint {
+                if !self.constraints.contains(&constraint.as_str()) {
+                    let suggestion = suggest(constraint, self.constraints.iter().copied());
+                    problems.push(unknown(context, "constraint", constraint, suggestion));
+                }
+            }
+            match ignored.column.as_ref().map(|c| c.split_once('.')) {
+                Some(Some((table, column))) => self.column(&mut problems, context, table, column),
+                Some(None) => problems.push(format!(
+                    "{context}: column {} should be written as table.column",
+                    ignored.column.as_deref().unwrap_or_default()
+                )),
+                None => {}
+            }
+        }
+
+        for (table, transforms) in cfg.transforms.iter().flatten() {
+            for (key, transform) in transforms {
+                let column = split_json_path(key).map_or(key.as_str(), |(column, _)| column);
+                self.column(&mut problems, "transforms", table, column);
+                for column in row_columns(transform) {
+                    self.column(&mut problems, "transforms", table, &column);
+                }
+            }
+        }
+
+        let partitions = cfg.export.as_ref().and_then(|e| e.partitions.as_ref());
+        for table in partitions.iter().flat_map(|p| p.keys()) {
+            self.table(&mut problems, "export.partitions", table);
+        }
+
+        problems.sort();
+        problems.dedup();
+        problems
+    }
+
+    fn table(&self, problems: &mut Vec<String>, context: &str, table: &str) -> Option<&[Column]> {
+        let columns = self.columns.get(table);
+        if columns.is_none() {
+            let suggestion = suggest(table, self.columns.keys().map(String::as_str));
+            problems.push(unknown(context, "table", table, suggestion));
+        }
+        columns.map(Vec::as_slice)
+    }
+
+    fn column(&self, problems: &mut Vec<String>, context: &str, table: &str, column: &str) {
+        if let Some(columns) = self.table(problems, context, table) {
+            if columns.iter().all(|c| c.name != column) {
+                let suggestion = suggest(column, columns.iter().map(|c| c.name.as_str()));
+                let column = format!("{table}.{column}");
+                problems.push(unknown(context, "column", &column, suggestion));
+            }
+        }
+    }
+
+    fn columns(&self, problems: &mut Vec<String>, context: &str, table: &str, columns: &[String]) {
+        for column in columns {
+            self.column(problems, context, table, column);
+        }
+    }
+}
+
+fn unknown(context: &str, kind: &str, name: &str, suggestion: Option<&str>) -> String {
+    match suggestion {
+        Some(suggestion) => format!("{context}: unknown {kind} {name}, did you mean {suggestion}?"),
+        None => format!("{context}: unknown {kind} {name}"),
+    }
+}
+
+/// the closest name, if it is near enough to be a typo
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    candidates
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE product(
+   id INT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+   name VARCHAR(255) NOT NULL,
+   price NUMERIC(10, 2) NOT NULL,
+   price_with_tax NUMERIC(10, 2) GENERATED ALWAYS AS (price * 1.2) STORED
+);
@@ -0,0 +1,92 @@
+extern crate pgsubset;
+
+use pgsubset::config::{Config, ImportOptions};
+use pgsubset::run;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+
+#[sqlx_database_tester::test(pool(variable = "pool", migrations = "./tests/generated/migrations"))]
+async fn test_export_import_generated_columns() {
+    let target_dir = "./tests/generated/export_csv";
+    sqlx::query("INSERT INTO product(name, price) VALUES ('product_1', 10), ('product_2', 20)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let cfg = Config {
+        target_table: Some("product".to_string()),
+        target_dir: target_dir.to_string(),
+        ..Default::default()
+    };
+    run::export(&pool, cfg).await.unwrap();
+
+    let product = tokio::fs::read_to_string(format!("{}/00-product.csv", target_dir))
+        .await
+        .unwrap();
+    assert_eq!(
+        product,
+        "id,name,price\n1,product_1,10.00\n2,product_2,20.00\n"
+    );
+
+    sqlx::query("TRUNCATE product")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let cfg = Config {
+        target_table: Some("product".to_string()),
+        target_dir: target_dir.to_string(),
+        ..Default::default()
+    };
+    run::import(&pool, cfg).await.unwrap();
+    teardown(target_dir).await;
+
+    let product = sqlx::query("SELECT id, price_with_tax::text AS price FROM product ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row: PgRow| {
+            format!(
+                "{},{}",
+                row.try_get::<i32, &str>("id").unwrap(),
+                row.try_get::<&str, &str>("price").unwrap()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    assert_eq!(product, "1,12.00\n2,24.00");
+}
+
+#[sqlx_database_tester::test(pool(variable = "pool", migrations = "./tests/generated/migrations"))]
+async fn test_import_missing_identity_column() {
+    let target_dir = "./tests/generated/identity_csv";
+    let cfg = Config {
+        target_table: Some("product".to_string()),
+        target_dir: target_dir.to_string(),
+        import: Some(ImportOptions {
+            fill_missing_columns: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    run::import(&pool, cfg).await.unwrap();
+
+    let product = sqlx::query("SELECT id, name FROM product ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row: PgRow| {
+            format!(
+                "{},{}",
+                row.try_get::<i32, &str>("id").unwrap(),
+                row.try_get::<&str, &str>("name").unwrap()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    assert_eq!(product, "1,product_1\n2,product_2");
+}
+
+async fn teardown(dir: &str) {
+    tokio::fs::remove_dir_all(dir).await.unwrap()
+}
@@ -0,0 +1,30 @@
+-- Add migration script here
+CREATE TABLE django_content_type(
+   id INT NOT NULL,
+   app_label VARCHAR(100) NOT NULL,
+   model VARCHAR(100) NOT NULL,
+   PRIMARY KEY(id)
+);
+
+CREATE TABLE blog_post(
+   id INT NOT NULL,
+   title VARCHAR(255) NOT NULL,
+   PRIMARY KEY(id)
+);
+
+CREATE TABLE blog_photo(
+   id INT NOT NULL,
+   url VARCHAR(255) NOT NULL,
+   PRIMARY KEY(id)
+);
+
+CREATE TABLE comment(
+   id INT NOT NULL,
+   content_type_id INT NOT NULL,
+   object_id INT NOT NULL,
+   body VARCHAR(255) NOT NULL,
+   PRIMARY KEY(id),
+   CONSTRAINT fk_content_type
+      FOREIGN KEY(content_type_id)
+	  REFERENCES django_content_type(id)
+);
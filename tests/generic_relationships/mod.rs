@@ -0,0 +1,70 @@
+extern crate pgsubset;
+
+use pgsubset::config::{Config, GenericRelationship};
+use pgsubset::run;
+
+#[sqlx_database_tester::test(pool(
+    variable = "pool",
+    migrations = "./tests/generic_relationships/migrations"
+))]
+async fn test_export_generic_relationships() {
+    let target_dir = "./tests/generic_relationships/export_csv";
+    sqlx::query("INSERT INTO django_content_type(id, app_label, model) VALUES (1, 'blog', 'post'), (2, 'blog', 'photo'), (3, 'blog', 'removed')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO blog_post(id, title) VALUES (1, 'post_1'), (2, 'post_2')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO blog_photo(id, url) VALUES (1, 'photo_1'), (2, 'photo_2')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO comment(id, content_type_id, object_id, body) VALUES (1, 1, 2, 'comment_1'), (2, 2, 1, 'comment_2'), (3, 3, 5, 'comment_3')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let cfg = Config {
+        target_table: Some("comment".to_string()),
+        target_dir: target_dir.to_string(),
+        generic_relationships: Some(vec![GenericRelationship {
+            source_table: "comment".to_string(),
+            type_column: "content_type_id".to_string(),
+            id_column: "object_id".to_string(),
+            dest_column: None,
+            targets: None,
+            lookup_query: Some(
+                "SELECT id::text, app_label || '_' || model FROM django_content_type".to_string(),
+            ),
+        }]),
+        ..Default::default()
+    };
+
+    run::export(&pool, cfg).await.unwrap();
+
+    let blog_post = read_table(target_dir, "blog_post").await;
+    let blog_photo = read_table(target_dir, "blog_photo").await;
+
+    teardown(target_dir).await;
+    assert_eq!(blog_post, "id,title\n2,post_2\n");
+    assert_eq!(blog_photo, "id,url\n1,photo_1\n");
+}
+
+async fn read_table(dir: &str, table: &str) -> String {
+    let mut entries = tokio::fs::read_dir(dir).await.unwrap();
+    while let Some(entry) = entries.next_entry().await.unwrap() {
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(&format!("-{table}.csv"))
+        {
+            return tokio::fs::read_to_string(entry.path()).await.unwrap();
+        }
+    }
+    panic!("{table} was not exported")
+}
+
+async fn teardown(dir: &str) {
+    tokio::fs::remove_dir_all(dir).await.unwrap()
+}
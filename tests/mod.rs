@@ -1 +1,9 @@
+mod config;
+mod generated;
+mod generic_relationships;
+mod partitioned;
+mod progress;
+mod remap;
 mod simple;
+mod typed;
+mod virtual_relationships;
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE customer(
+   id INT NOT NULL,
+   name VARCHAR(255) NOT NULL,
+   PRIMARY KEY(id)
+);
+
+CREATE TABLE orders(
+   id INT NOT NULL,
+   created DATE NOT NULL,
+   customer_id INT REFERENCES customer(id),
+   PRIMARY KEY(id, created)
+) PARTITION BY RANGE (created);
+
+CREATE TABLE orders_2021 PARTITION OF orders FOR VALUES FROM ('2021-01-01') TO ('2022-01-01');
+CREATE TABLE orders_2022 PARTITION OF orders FOR VALUES FROM ('2022-01-01') TO ('2023-01-01');
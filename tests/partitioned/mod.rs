@@ -0,0 +1,77 @@
+extern crate pgsubset;
+use std::collections::HashMap;
+
+use pgsubset::config::{Config, ExportOptions};
+use pgsubset::run;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+
+#[sqlx_database_tester::test(pool(
+    variable = "pool",
+    migrations = "./tests/partitioned/migrations"
+))]
+async fn test_export_import_partitioned() {
+    let target_dir = "./tests/partitioned/export_csv";
+    sqlx::query("INSERT INTO customer(id, name) VALUES (1, 'customer_1'), (2, 'customer_2')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO orders(id, created, customer_id) VALUES (1, '2021-05-01', 1), (2, '2022-05-01', 2)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let mut partitions = HashMap::new();
+    partitions.insert("orders".to_string(), vec!["orders_2022".to_string()]);
+    let cfg = Config {
+        target_table: Some("orders".to_string()),
+        target_dir: target_dir.to_string(),
+        export: Some(ExportOptions {
+            partitions: Some(partitions),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    run::export(&pool, cfg).await.unwrap();
+
+    let orders = tokio::fs::read_to_string(format!("{}/01-orders.csv", target_dir))
+        .await
+        .unwrap();
+    let partition = tokio::fs::metadata(format!("{}/02-orders_2022.csv", target_dir)).await;
+    assert!(partition.is_err());
+    assert_eq!(orders, "id,created,customer_id\n2,2022-05-01,2\n");
+
+    sqlx::query("TRUNCATE orders, customer")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let cfg = Config {
+        target_table: Some("orders".to_string()),
+        target_dir: target_dir.to_string(),
+        ..Default::default()
+    };
+    run::import(&pool, cfg).await.unwrap();
+    teardown(target_dir).await;
+
+    let orders = sqlx::query("SELECT tableoid::regclass::text AS partition, id FROM orders")
+        .fetch_all(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row: PgRow| {
+            format!(
+                "{},{}",
+                row.try_get::<&str, &str>("partition").unwrap(),
+                row.try_get::<i32, &str>("id").unwrap()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    assert_eq!(orders, "orders_2022,2");
+}
+
+async fn teardown(dir: &str) {
+    tokio::fs::remove_dir_all(dir).await.unwrap()
+}
@@ -0,0 +1,38 @@
+extern crate pgsubset;
+
+use pgsubset::progress::{json_line, LogFormat};
+use serde_json::json;
+
+#[test]
+fn test_json_line() {
+    let line = json_line(
+        "table_finished",
+        1760000000.5,
+        json!({
+            "action": "export",
+            "table": "user",
+            "rows": 1200,
+            "estimated_rows": null,
+        }),
+    );
+    assert_eq!(
+        line,
+        r#"{"event":"table_finished","timestamp":1760000000.5,"action":"export","table":"user","rows":1200,"estimated_rows":null}"#
+    );
+
+    // fields which aren't an object are left out
+    assert_eq!(
+        json_line("truncated", 0.0, json!(["user"])),
+        r#"{"event":"truncated","timestamp":0.0}"#
+    );
+}
+
+#[test]
+fn test_log_format() {
+    assert!("text".parse::<LogFormat>().unwrap() == LogFormat::Text);
+    assert!("json".parse::<LogFormat>().unwrap() == LogFormat::Json);
+    assert_eq!(
+        "yaml".parse::<LogFormat>().err().unwrap().to_string(),
+        "unknown log format yaml"
+    );
+}
@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE author(
+   id SERIAL PRIMARY KEY,
+   name VARCHAR(255) NOT NULL
+);
+
+CREATE TABLE book(
+   id SERIAL PRIMARY KEY,
+   author_id INT NOT NULL REFERENCES author(id),
+   sequel_id INT REFERENCES book(id),
+   title VARCHAR(255) NOT NULL
+);
@@ -0,0 +1,97 @@
+extern crate pgsubset;
+
+use pgsubset::config::{Config, ImportOptions};
+use pgsubset::run;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+
+#[sqlx_database_tester::test(pool(variable = "pool", migrations = "./tests/remap/migrations"))]
+async fn test_import_remap_keys() {
+    let target_dir = "./tests/remap/csv";
+    sqlx::query("INSERT INTO author(name) VALUES ('existing_1'), ('existing_2')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO book(author_id, title) VALUES (1, 'existing_1')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let cfg = Config {
+        target_table: Some("book".to_string()),
+        target_dir: target_dir.to_string(),
+        import: Some(ImportOptions {
+            remap_keys: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    run::import(&pool, cfg).await.unwrap();
+    let authors = sqlx::query("SELECT id, name FROM author ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row: PgRow| {
+            format!(
+                "{},{}",
+                row.try_get::<i32, &str>("id").unwrap(),
+                row.try_get::<&str, &str>("name").unwrap()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let books = sqlx::query("SELECT id, author_id, sequel_id, title FROM book ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row: PgRow| {
+            format!(
+                "{},{},{},{}",
+                row.try_get::<i32, &str>("id").unwrap(),
+                row.try_get::<i32, &str>("author_id").unwrap(),
+                row.try_get::<Option<i32>, &str>("sequel_id")
+                    .unwrap()
+                    .map(|id| id.to_string())
+                    .unwrap_or_default(),
+                row.try_get::<&str, &str>("title").unwrap()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    assert_eq!(
+        authors,
+        "1,existing_1\n2,existing_2\n3,author_1\n4,author_2"
+    );
+    assert_eq!(books, "1,1,,existing_1\n2,3,,book_1\n3,4,2,book_2");
+}
+
+#[sqlx_database_tester::test(pool(variable = "pool", migrations = "./tests/remap/migrations"))]
+async fn test_import_remap_unmapped_keys() {
+    let target_dir = "./tests/remap/unmapped";
+    sqlx::query("INSERT INTO author(name) VALUES ('existing_1'), ('existing_2'), ('existing_3'), ('existing_4'), ('existing_5')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let cfg = Config {
+        target_table: Some("book".to_string()),
+        target_dir: target_dir.to_string(),
+        import: Some(ImportOptions {
+            remap_keys: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let err = run::import(&pool, cfg).await.err().unwrap();
+    assert!(
+        format!("{err:#}").contains("1 rows of book reference author rows missing from the import")
+    );
+    let count: i64 = sqlx::query_scalar("SELECT count(*) FROM book")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE account(
+   id INT NOT NULL,
+   balance NUMERIC(10, 2) NOT NULL,
+   profile JSONB,
+   emails TEXT[],
+   PRIMARY KEY(id)
+);
@@ -0,0 +1,128 @@
+extern crate pgsubset;
+
+use pgsubset::config::Config;
+use pgsubset::run;
+
+#[sqlx_database_tester::test(pool(variable = "pool", migrations = "./tests/typed/migrations"))]
+async fn test_type_specific_transforms() {
+    let target_dir = "./tests/typed/export_csv";
+    sqlx::query(
+        r#"INSERT INTO account(id, balance, profile, emails) VALUES
+(1, 1234.56, '{"email": "a@b.com", "contacts": [{"email": "c@d.com", "kind": "work"}]}', '{"a@b.com", NULL}')"#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let cfg: Config = toml::from_str(&format!(
+        r#"
+target_table = "account"
+target_dir = "{target_dir}"
+database_url = ""
+
+[transforms.account]
+balance = {{ kind = "round", digits = -2 }}
+profile = {{ kind = "json_keys", keys = ["email"], transform = {{ kind = "constant", value = "x@example.com" }} }}
+emails = {{ kind = "array_elements", transform = {{ kind = "regex_replace", pattern = "@.*", replacement = "@example.com" }} }}
+"#
+    ))
+    .unwrap();
+
+    run::export(&pool, cfg).await.unwrap();
+
+    let account = tokio::fs::read_to_string(format!("{}/00-account.csv", target_dir))
+        .await
+        .unwrap();
+
+    teardown(target_dir).await;
+    assert_eq!(
+        account,
+        concat!(
+            "id,balance,profile,emails\n",
+            r#"1,1200,"{""email"":""x@example.com"",""contacts"":[{""kind"":""work"",""email"":""x@example.com""}]}","{""a@example.com"",NULL}""#,
+            "\n"
+        )
+    );
+}
+
+#[sqlx_database_tester::test(pool(variable = "pool", migrations = "./tests/typed/migrations"))]
+async fn test_incompatible_transforms() {
+    let target_dir = "./tests/typed/export_incompatible_csv";
+    let config = |transform: &str| {
+        toml::from_str::<Config>(&format!(
+            r#"
+target_table = "account"
+target_dir = "{target_dir}"
+database_url = ""
+
+[transforms.account]
+{transform}
+"#
+        ))
+        .unwrap()
+    };
+
+    for transform in [
+        r#"id = "clear_field""#,
+        r#"balance = "set_null""#,
+        r#"profile = { kind = "round", digits = 0 }"#,
+        r#"emails = { kind = "json_keys", keys = ["email"], transform = "email_en" }"#,
+        r#""emails.$.email" = "email_en""#,
+    ] {
+        let err = run::export(&pool, config(transform)).await.unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid transform for account."),
+            "{transform}: {err:#}"
+        );
+    }
+    teardown(target_dir).await;
+}
+
+#[sqlx_database_tester::test(pool(variable = "pool", migrations = "./tests/typed/migrations"))]
+async fn test_json_path_transforms() {
+    let target_dir = "./tests/typed/export_json_path_csv";
+    sqlx::query(
+        r#"INSERT INTO account(id, balance, profile) VALUES
+(1, 10, '{"email": "a@b.com", "contacts": [{"email": "c@d.com", "kind": "work"}, {"kind": "home"}]}'),
+(2, 20, NULL),
+(3, 30, '{"email": "e@f.com", "id": 123456789012345678901234, "score": 1.10, "contacts": []}')"#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let cfg: Config = toml::from_str(&format!(
+        r#"
+target_table = "account"
+target_dir = "{target_dir}"
+database_url = ""
+
+[transforms.account]
+"profile.$.contacts.*.email" = {{ kind = "constant", value = "x@example.com" }}
+"profile.$.contacts.1.kind" = "set_null"
+"#
+    ))
+    .unwrap();
+
+    run::export(&pool, cfg).await.unwrap();
+
+    let account = tokio::fs::read_to_string(format!("{}/00-account.csv", target_dir))
+        .await
+        .unwrap();
+
+    teardown(target_dir).await;
+    assert_eq!(
+        account,
+        concat!(
+            "id,balance,profile,emails\n",
+            r#"1,10.00,"{""email"":""a@b.com"",""contacts"":[{""kind"":""work"",""email"":""x@example.com""},{""kind"":null}]}","#,
+            "\n2,20.00,,\n",
+            r#"3,30.00,"{""id"":123456789012345678901234,""email"":""e@f.com"",""score"":1.10,""contacts"":[]}","#,
+            "\n"
+        )
+    );
+}
+
+async fn teardown(dir: &str) {
+    tokio::fs::remove_dir_all(dir).await.unwrap()
+}
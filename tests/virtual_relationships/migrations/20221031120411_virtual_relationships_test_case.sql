@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TABLE legacy_user(
+   id INT NOT NULL,
+   name VARCHAR(255) NOT NULL,
+   PRIMARY KEY(id)
+);
+
+CREATE TABLE legacy_order(
+   id INT NOT NULL,
+   user_id INT NOT NULL,
+   name VARCHAR(255) NOT NULL,
+   PRIMARY KEY(id)
+);
+
+CREATE TABLE attachment(
+   id INT NOT NULL,
+   owner_kind VARCHAR(255) NOT NULL,
+   owner_id INT NOT NULL,
+   PRIMARY KEY(id)
+);
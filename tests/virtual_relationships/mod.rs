@@ -0,0 +1,71 @@
+extern crate pgsubset;
+
+use pgsubset::config::{Config, Discriminator, VirtualRelationship};
+use pgsubset::run;
+
+#[sqlx_database_tester::test(pool(
+    variable = "pool",
+    migrations = "./tests/virtual_relationships/migrations"
+))]
+async fn test_export_virtual_relationships() {
+    let target_dir = "./tests/virtual_relationships/export_csv";
+    sqlx::query(
+        "INSERT INTO legacy_user(id, name) VALUES (1, 'user_1'), (2, 'user_2'), (3, 'user_3')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO legacy_order(id, user_id, name) VALUES (1, 1, 'order_1'), (2, 1, 'order_2')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO attachment(id, owner_kind, owner_id) VALUES (1, 'order', 1), (2, 'user', 3)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    let cfg = Config {
+        target_table: Some("attachment".to_string()),
+        target_dir: target_dir.to_string(),
+        virtual_relationships: Some(vec![
+            VirtualRelationship {
+                source_table: "attachment".to_string(),
+                source_columns: vec!["owner_id".to_string()],
+                dest_table: "legacy_order".to_string(),
+                dest_columns: vec!["id".to_string()],
+                discriminator: Some(Discriminator {
+                    column: "owner_kind".to_string(),
+                    value: "order".to_string(),
+                }),
+            },
+            VirtualRelationship {
+                source_table: "legacy_order".to_string(),
+                source_columns: vec!["user_id".to_string()],
+                dest_table: "legacy_user".to_string(),
+                dest_columns: vec!["id".to_string()],
+                discriminator: None,
+            },
+        ]),
+        ..Default::default()
+    };
+
+    run::export(&pool, cfg).await.unwrap();
+
+    let legacy_user = tokio::fs::read_to_string(format!("{}/00-legacy_user.csv", target_dir))
+        .await
+        .unwrap();
+    let legacy_order = tokio::fs::read_to_string(format!("{}/01-legacy_order.csv", target_dir))
+        .await
+        .unwrap();
+
+    teardown(target_dir).await;
+    assert_eq!(legacy_user, "id,name\n1,user_1\n");
+    assert_eq!(legacy_order, "id,user_id,name\n1,1,order_1\n");
+}
+
+async fn teardown(dir: &str) {
+    tokio::fs::remove_dir_all(dir).await.unwrap()
+}